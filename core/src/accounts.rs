@@ -0,0 +1,184 @@
+use crate::error::ApplicationError;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::{params, Connection, ErrorCode};
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// hex chars of the salt appended to every hash
+static SALT_LEN: usize = 16;
+/// give a running ankisyncd this long to release its lock
+static BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// a sync account in ankisyncd's auth db
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub username: String,
+    /// sha256 hex of username+password+salt,followed by the salt
+    hash: String,
+}
+/// ankisyncd's auth db,table auth(username,hash)
+///
+/// stands in for `ankisyncd user -a/-d/-p/-l`
+pub struct AccountStore {
+    conn: Connection,
+}
+impl AccountStore {
+    /// open or create the db,ankisyncd creates the same table on start
+    pub fn open(path: &Path) -> Result<AccountStore, ApplicationError> {
+        let conn = Connection::open(path).map_err(db_error)?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(db_error)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS auth (username VARCHAR PRIMARY KEY, hash VARCHAR)",
+            [],
+        )
+        .map_err(db_error)?;
+        Ok(AccountStore { conn })
+    }
+    pub fn list(&self) -> Result<Vec<Account>, ApplicationError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT username, hash FROM auth ORDER BY username")
+            .map_err(db_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(Account {
+                    username: row.get(0)?,
+                    hash: row.get(1)?,
+                })
+            })
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+    pub fn add(&self, username: &str, password: &str) -> Result<(), ApplicationError> {
+        let hash = pass_hash(username, password, &new_salt()?);
+        self.conn
+            .execute(
+                "INSERT INTO auth (username, hash) VALUES (?1, ?2)",
+                params![username, hash],
+            )
+            .map_err(|e| match e.sqlite_error_code() {
+                Some(ErrorCode::ConstraintViolation) => {
+                    ApplicationError::UserExists(username.into())
+                }
+                _ => db_error(e),
+            })?;
+        Ok(())
+    }
+    pub fn delete(&self, username: &str) -> Result<(), ApplicationError> {
+        let n = self
+            .conn
+            .execute("DELETE FROM auth WHERE username = ?1", params![username])
+            .map_err(db_error)?;
+        found(n, username)
+    }
+    pub fn set_password(&self, username: &str, password: &str) -> Result<(), ApplicationError> {
+        let hash = pass_hash(username, password, &new_salt()?);
+        let n = self
+            .conn
+            .execute(
+                "UPDATE auth SET hash = ?2 WHERE username = ?1",
+                params![username, hash],
+            )
+            .map_err(db_error)?;
+        found(n, username)
+    }
+}
+
+fn found(rows: usize, username: &str) -> Result<(), ApplicationError> {
+    if rows == 0 {
+        Err(ApplicationError::UserNotFound(username.into()))
+    } else {
+        Ok(())
+    }
+}
+/// busy/locked means ankisyncd holds the db,everything else is passed on
+fn db_error(e: rusqlite::Error) -> ApplicationError {
+    match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+            ApplicationError::AuthDbLocked
+        }
+        _ => ApplicationError::AuthDb(e),
+    }
+}
+/// 8 random bytes as 16 hex chars
+fn new_salt() -> Result<String, ApplicationError> {
+    let mut bytes = [0u8; SALT_LEN / 2];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| ApplicationError::Unknown)?;
+    Ok(hex(&bytes))
+}
+fn pass_hash(username: &str, password: &str, salt: &str) -> String {
+    let d = digest(
+        &SHA256,
+        format!("{}{}{}", username, password, salt).as_bytes(),
+    );
+    hex(d.as_ref()) + salt
+}
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{:02x}", b);
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Account {
+        fn verify(&self, password: &str) -> bool {
+            let salt = &self.hash[self.hash.len() - SALT_LEN..];
+            pass_hash(&self.username, password, salt) == self.hash
+        }
+    }
+    fn get(store: &AccountStore, username: &str) -> Account {
+        let accounts = store.list().unwrap();
+        accounts
+            .into_iter()
+            .find(|a| a.username == username)
+            .unwrap()
+    }
+
+    #[test]
+    fn add_change_delete() {
+        let path = std::env::temp_dir().join(format!("deployer-auth-{}.db", std::process::id()));
+        let store = AccountStore::open(&path).unwrap();
+        store.add("alice", "secret").unwrap();
+        assert!(matches!(
+            store.add("alice", "again"),
+            Err(ApplicationError::UserExists(_))
+        ));
+        assert!(get(&store, "alice").verify("secret"));
+
+        store.set_password("alice", "changed").unwrap();
+        let alice = get(&store, "alice");
+        assert!(alice.verify("changed") && !alice.verify("secret"));
+
+        store.delete("alice").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        assert!(matches!(
+            store.delete("alice"),
+            Err(ApplicationError::UserNotFound(_))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn hash_matches_ankisyncd() {
+        // sha256 hex of username+password+salt then the salt,as ankisyncd user -a writes it
+        let hash = pass_hash("bob", "pw", "0011223344556677");
+        assert_eq!(
+            hash,
+            "708cbbd0acf61d70e1dbe60b5c4abab42c3deda3ad4f8faaf3420793497f33a1\
+             0011223344556677"
+        );
+        let bob = Account {
+            username: "bob".into(),
+            hash,
+        };
+        assert!(bob.verify("pw"));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::ApplicationError;
+use crate::safe_write::safe_write;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// ankisyncd's port unless the deployer was told otherwise
+pub static DEFAULT_SYNC_PORT: u16 = 27701;
+
+static SYNCADDR: &str = "syncaddr";
+/// only rewritten when the addon version in use has it
+static MEDIAADDR: &str = "mediaaddr";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        })
+    }
+}
+/// address PC and phone Anki sync to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncAddr {
+    pub scheme: Scheme,
+    pub host: String,
+    pub port: u16,
+}
+impl SyncAddr {
+    pub fn new(scheme: Scheme, host: &str, port: u16) -> SyncAddr {
+        SyncAddr {
+            scheme,
+            host: host.into(),
+            port,
+        }
+    }
+    /// split a sync url up,None unless it has scheme,host and port
+    pub fn parse(url: &str) -> Option<SyncAddr> {
+        let (scheme, rest) = url.split_once("://")?;
+        let scheme = match scheme {
+            "http" => Scheme::Http,
+            "https" => Scheme::Https,
+            _ => return None,
+        };
+        let (host, port) = rest.split('/').next()?.rsplit_once(':')?;
+        // ipv6 literals come in brackets
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Some(SyncAddr::new(scheme, host, port.parse().ok()?))
+    }
+    /// https://192.168.1.2:27701
+    pub fn base_url(&self) -> String {
+        // ipv6 literals need brackets in urls
+        match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("{}://[{}]:{}", self.scheme, ip, self.port),
+            _ => format!("{}://{}:{}", self.scheme, self.host, self.port),
+        }
+    }
+    /// https://192.168.1.2:27701/
+    pub fn sync_url(&self) -> String {
+        format!("{}/", self.base_url())
+    }
+    /// https://192.168.1.2:27701/msync/
+    pub fn media_url(&self) -> String {
+        format!("{}/msync/", self.base_url())
+    }
+}
+
+/// config.json of the ankisyncd addon in PC Anki's addons21 dir
+///
+/// ```json
+/// {
+///     "syncaddr": "https://192.168.1.2:27701/"
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AddonConfig {
+    path: PathBuf,
+    values: Map<String, Value>,
+}
+impl AddonConfig {
+    /// config.json in the addon dir
+    pub fn path_in(ankisyncd_dir: &Path) -> PathBuf {
+        ankisyncd_dir.join("config.json")
+    }
+    pub async fn load(path: impl Into<PathBuf>) -> Result<AddonConfig, ApplicationError> {
+        let path = path.into();
+        let contents = async_std::fs::read_to_string(&path).await?;
+        Self::parse(path, &contents)
+    }
+    fn parse(path: PathBuf, contents: &str) -> Result<AddonConfig, ApplicationError> {
+        let values = match serde_json::from_str(contents)? {
+            Value::Object(values) => values,
+            _ => return Err(ApplicationError::ConfigNotObject(path)),
+        };
+        Ok(AddonConfig { path, values })
+    }
+    pub fn sync_addr(&self) -> Option<&str> {
+        self.values.get(SYNCADDR).and_then(Value::as_str)
+    }
+    /// syncaddr split up,None unless it has scheme,host and port
+    pub fn parsed_sync_addr(&self) -> Option<SyncAddr> {
+        SyncAddr::parse(self.sync_addr()?)
+    }
+    /// point syncaddr (and mediaaddr if present) at addr
+    ///
+    /// return true if the address changed
+    pub fn set_sync_addr(&mut self, addr: &SyncAddr) -> bool {
+        let url = addr.sync_url();
+        let changed = self.sync_addr() != Some(url.as_str());
+        self.values.insert(SYNCADDR.into(), Value::String(url));
+        if self.values.contains_key(MEDIAADDR) {
+            self.values
+                .insert(MEDIAADDR.into(), Value::String(addr.media_url()));
+        }
+        changed
+    }
+    pub(crate) fn to_json(&self) -> Result<String, ApplicationError> {
+        let mut buf = Vec::new();
+        let fmt = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, fmt);
+        serde::Serialize::serialize(&self.values, &mut ser)?;
+        buf.push(b'\n');
+        Ok(String::from_utf8(buf)?)
+    }
+    /// write config.json through safe_write,a crash never leaves it truncated
+    pub async fn save(&self) -> Result<(), ApplicationError> {
+        safe_write(&self.path, self.to_json()?.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_other_keys() {
+        let src = r#"{"syncaddr": "http://10.0.0.1:27701/", "mediaaddr": "", "extra": [1, 2]}"#;
+        let mut c = AddonConfig::parse("config.json".into(), src).unwrap();
+        let addr = SyncAddr::new(Scheme::Https, "192.168.1.2", 27701);
+        assert!(c.set_sync_addr(&addr));
+        assert!(!c.set_sync_addr(&addr));
+
+        let out = c.to_json().unwrap();
+        let back = AddonConfig::parse("config.json".into(), &out).unwrap();
+        assert_eq!(back.sync_addr(), Some("https://192.168.1.2:27701/"));
+        assert_eq!(back.parsed_sync_addr(), Some(addr));
+        assert_eq!(back.values["mediaaddr"], "https://192.168.1.2:27701/msync/");
+        assert_eq!(back.values["extra"], serde_json::json!([1, 2]));
+        // key order survives
+        assert!(out.find("syncaddr").unwrap() < out.find("extra").unwrap());
+    }
+
+    #[test]
+    fn ipv6_is_bracketed() {
+        let addr = SyncAddr::new(Scheme::Http, "fe80::1", 8080);
+        assert_eq!(addr.sync_url(), "http://[fe80::1]:8080/");
+
+        let mut c = AddonConfig::parse("config.json".into(), "{}").unwrap();
+        assert_eq!(c.parsed_sync_addr(), None);
+        c.set_sync_addr(&addr);
+        assert_eq!(c.parsed_sync_addr(), Some(addr));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::addon_config::{AddonConfig, SyncAddr};
+use crate::error::ApplicationError;
+use rusqlite::{params, Connection, OpenFlags};
+use serde_pickle::{DeOptions, HashableValue, SerOptions, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Anki release,2.1.x up to 2.1.66 and calendar versions like 23.10 after
+///
+/// orders by release date,23.10 is newer than 2.1.66
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnkiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+impl AnkiVersion {
+    /// oldest release the ankisyncd addon works with
+    pub const MIN_SUPPORTED: AnkiVersion = AnkiVersion::new(2, 1, 11);
+    /// first release with a custom sync server preference of its own
+    pub const BUILTIN_SYNC: AnkiVersion = AnkiVersion::new(2, 1, 57);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> AnkiVersion {
+        AnkiVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+    /// Anki's int_version,the patch for 2.1.x and YYMMPP since 23.10
+    pub fn from_int_version(v: i64) -> Option<AnkiVersion> {
+        match v {
+            0..=99 => Some(AnkiVersion::new(2, 1, v as u32)),
+            10000..=999999 => Some(AnkiVersion::new(
+                (v / 10000) as u32,
+                (v / 100 % 100) as u32,
+                (v % 100) as u32,
+            )),
+            _ => None,
+        }
+    }
+    pub fn is_supported(&self) -> bool {
+        *self >= AnkiVersion::MIN_SUPPORTED
+    }
+    /// sync server is set in Anki's preferences,no addon needed
+    pub fn has_builtin_sync(&self) -> bool {
+        *self >= AnkiVersion::BUILTIN_SYNC
+    }
+}
+impl FromStr for AnkiVersion {
+    type Err = ApplicationError;
+
+    /// 2.1.36,23.10,24.06.3 or a line like anki --version's "Anki 2.1.36"
+    fn from_str(s: &str) -> Result<AnkiVersion, ApplicationError> {
+        let bad = || ApplicationError::AnkiVersion(s.trim().into());
+        // newer builds print a commit hash after the version
+        let token = s
+            .split_whitespace()
+            .find(|t| t.starts_with(|c: char| c.is_ascii_digit()))
+            .ok_or_else(bad)?;
+        let parts = token
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| bad())?;
+        match parts[..] {
+            [major, minor] => Ok(AnkiVersion::new(major, minor, 0)),
+            [major, minor, patch] => Ok(AnkiVersion::new(major, minor, patch)),
+            _ => Err(bad()),
+        }
+    }
+}
+impl fmt::Display for AnkiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let AnkiVersion {
+            major,
+            minor,
+            patch,
+        } = *self;
+        if major <= 2 {
+            write!(f, "{}.{}.{}", major, minor, patch)
+        } else if patch == 0 {
+            write!(f, "{}.{:02}", major, minor)
+        } else {
+            write!(f, "{}.{:02}.{}", major, minor, patch)
+        }
+    }
+}
+
+/// Anki's own entry in prefs21.db,not a profile
+static GLOBAL: &str = "_global";
+static LAST_RUN_VERSION: &str = "last_run_version";
+static CUSTOM_SYNC_URL: &str = "customSyncUrl";
+/// endpoint the server redirected Anki to,stale once the url changes
+static CURRENT_SYNC_URL: &str = "currentSyncUrl";
+
+/// prefs21.db in Anki's profile dir,one pickled dict per profile
+///
+/// Anki reads a profile when it opens it and writes it back on close,
+/// changes made while PC Anki runs are lost
+pub struct Profiles {
+    conn: Connection,
+}
+impl Profiles {
+    pub fn path_in(anki_profile_dir: &Path) -> PathBuf {
+        anki_profile_dir.join("prefs21.db")
+    }
+    pub fn open(path: &Path) -> Result<Profiles, ApplicationError> {
+        // sqlite would create an empty db where Anki never ran
+        if !path.exists() {
+            return Err(ApplicationError::NoAnkiProfile(path.into()));
+        }
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .map_err(profile_err)?;
+        Ok(Profiles { conn })
+    }
+    /// profile names,without Anki's own _global entry
+    pub fn names(&self) -> Result<Vec<String>, ApplicationError> {
+        let mut stmt = self
+            .conn
+            .prepare("select name from profiles where name != ? order by name")
+            .map_err(profile_err)?;
+        let rows = stmt
+            .query_map([GLOBAL], |r| r.get(0))
+            .map_err(profile_err)?;
+        rows.collect::<Result<_, _>>().map_err(profile_err)
+    }
+    fn load(&self, name: &str) -> Result<BTreeMap<HashableValue, Value>, ApplicationError> {
+        let data: Vec<u8> = self
+            .conn
+            .query_row("select data from profiles where name = ?", [name], |r| {
+                r.get(0)
+            })
+            .map_err(profile_err)?;
+        // old profiles pickle Qt window geometry,Anki does without it
+        let options = DeOptions::new().replace_unresolved_globals();
+        match serde_pickle::value_from_slice(&data, options)? {
+            Value::Dict(profile) => Ok(profile),
+            _ => Err(ApplicationError::AnkiProfile(format!(
+                "profile {} is not a dict",
+                name
+            ))),
+        }
+    }
+    fn save(
+        &self,
+        name: &str,
+        profile: BTreeMap<HashableValue, Value>,
+    ) -> Result<(), ApplicationError> {
+        let data = serde_pickle::value_to_vec(&Value::Dict(profile), SerOptions::new())?;
+        self.conn
+            .execute(
+                "update profiles set data = ? where name = ?",
+                params![data, name],
+            )
+            .map_err(profile_err)?;
+        Ok(())
+    }
+    /// release which last opened this db
+    pub fn last_run_version(&self) -> Option<AnkiVersion> {
+        match self.load(GLOBAL).ok()?.get(&key(LAST_RUN_VERSION))? {
+            Value::I64(v) => AnkiVersion::from_int_version(*v),
+            _ => None,
+        }
+    }
+    /// sync server of profile name,None for AnkiWeb
+    pub fn custom_sync_url(&self, name: &str) -> Result<Option<String>, ApplicationError> {
+        Ok(match self.load(name)?.get(&key(CUSTOM_SYNC_URL)) {
+            Some(Value::String(url)) if !url.is_empty() => Some(url.clone()),
+            _ => None,
+        })
+    }
+    /// point profile name at url,None goes back to AnkiWeb
+    ///
+    /// return true if the url changed
+    pub fn set_custom_sync_url(
+        &self,
+        name: &str,
+        url: Option<&str>,
+    ) -> Result<bool, ApplicationError> {
+        if self.custom_sync_url(name)?.as_deref() == url {
+            return Ok(false);
+        }
+        let mut profile = self.load(name)?;
+        // as Anki's own setter does
+        profile.insert(key(CURRENT_SYNC_URL), Value::None);
+        let url = url.map_or(Value::None, |u| Value::String(u.into()));
+        profile.insert(key(CUSTOM_SYNC_URL), url);
+        self.save(name, profile)?;
+        Ok(true)
+    }
+}
+fn key(name: &str) -> HashableValue {
+    HashableValue::String(name.into())
+}
+fn profile_err(e: rusqlite::Error) -> ApplicationError {
+    ApplicationError::AnkiProfile(e.to_string())
+}
+
+/// where PC Anki takes the sync server address from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncTarget {
+    /// config.json of the ankisyncd addon in this dir,Anki before 2.1.57
+    Addon(PathBuf),
+    /// custom sync url of every profile in this prefs21.db
+    Profiles(PathBuf),
+}
+impl SyncTarget {
+    /// the file holding the address
+    pub fn path(&self) -> PathBuf {
+        match self {
+            SyncTarget::Addon(dir) => AddonConfig::path_in(dir),
+            SyncTarget::Profiles(db) => db.clone(),
+        }
+    }
+    /// address PC Anki syncs to,the first profile with one for Profiles
+    pub async fn current(&self) -> Option<SyncAddr> {
+        match self {
+            SyncTarget::Addon(dir) => AddonConfig::load(AddonConfig::path_in(dir))
+                .await
+                .ok()?
+                .parsed_sync_addr(),
+            SyncTarget::Profiles(db) => {
+                let profiles = Profiles::open(db).ok()?;
+                let names = profiles.names().ok()?;
+                names
+                    .iter()
+                    .find_map(|n| profiles.custom_sync_url(n).ok().flatten())
+                    .and_then(|url| SyncAddr::parse(&url))
+            }
+        }
+    }
+    /// point PC Anki at addr,return true if the address changed
+    pub async fn set(&self, addr: &SyncAddr) -> Result<bool, ApplicationError> {
+        match self {
+            SyncTarget::Addon(dir) => {
+                let mut conf = AddonConfig::load(AddonConfig::path_in(dir)).await?;
+                let changed = conf.set_sync_addr(addr);
+                conf.save().await?;
+                Ok(changed)
+            }
+            SyncTarget::Profiles(db) => {
+                let profiles = Profiles::open(db)?;
+                let url = addr.sync_url();
+                let mut changed = false;
+                for name in profiles.names()? {
+                    changed |= profiles.set_custom_sync_url(&name, Some(&url))?;
+                }
+                Ok(changed)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_order_by_release() {
+        let v = |s: &str| s.parse::<AnkiVersion>().unwrap();
+        assert_eq!(v("Anki 2.1.36\n"), AnkiVersion::new(2, 1, 36));
+        assert_eq!(v("2.1.66 (70506aeb)"), AnkiVersion::new(2, 1, 66));
+        assert_eq!(v("24.06.3").to_string(), "24.06.3");
+        assert_eq!(v("23.10").to_string(), "23.10");
+        assert!(v("2.1.9") < v("2.1.10") && v("2.1.66") < v("23.10"));
+        assert!(!v("2.1.10").is_supported() && v("24.06").is_supported());
+        assert!(!v("2.1.54").has_builtin_sync() && v("23.10").has_builtin_sync());
+        assert!("2.1.x".parse::<AnkiVersion>().is_err());
+        assert_eq!(AnkiVersion::from_int_version(231000), Some(v("23.10")));
+        assert_eq!(AnkiVersion::from_int_version(54), Some(v("2.1.54")));
+    }
+
+    #[test]
+    fn sets_sync_url_in_every_profile() {
+        let dir = std::env::temp_dir().join(format!("deploy-core-prefs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Profiles::path_in(&dir);
+        let conn = Connection::open(&db).unwrap();
+        conn.execute(
+            "create table profiles (name text primary key collate nocase, data blob not null)",
+            [],
+        )
+        .unwrap();
+        let pickle = |pairs: &[(&str, Value)]| {
+            let dict = pairs.iter().map(|(k, v)| (key(k), v.clone())).collect();
+            serde_pickle::value_to_vec(&Value::Dict(dict), SerOptions::new()).unwrap()
+        };
+        let global = pickle(&[(LAST_RUN_VERSION, Value::I64(240600))]);
+        let user = pickle(&[("syncUser", Value::String("me".into()))]);
+        for (name, data) in [(GLOBAL, &global), ("User 1", &user), ("Other", &user)] {
+            conn.execute("insert into profiles values (?, ?)", params![name, data])
+                .unwrap();
+        }
+        drop(conn);
+
+        let profiles = Profiles::open(&db).unwrap();
+        assert_eq!(
+            profiles.last_run_version(),
+            Some(AnkiVersion::new(24, 6, 0))
+        );
+        assert_eq!(profiles.names().unwrap(), vec!["Other", "User 1"]);
+        let target = SyncTarget::Profiles(db.clone());
+        let addr = SyncAddr::new(crate::addon_config::Scheme::Https, "192.168.1.2", 27701);
+        async_std::task::block_on(async {
+            assert!(target.set(&addr).await.unwrap());
+            assert!(!target.set(&addr).await.unwrap());
+            assert_eq!(target.current().await, Some(addr));
+        });
+        // other keys survive the roundtrip
+        let back = profiles.load("User 1").unwrap();
+        assert_eq!(back[&key("syncUser")], Value::String("me".into()));
+        assert_eq!(back[&key(CURRENT_SYNC_URL)], Value::None);
+        profiles.set_custom_sync_url("User 1", None).unwrap();
+        assert_eq!(profiles.custom_sync_url("User 1").unwrap(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,385 @@
+use crate::error::ApplicationError;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use time::format_description::BorrowedFormatItem;
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// ankisyncd keeps every user's collection and media under this dir
+static COLLECTIONS_DIR: &str = "collections";
+/// index of an archive's files,written last so a truncated archive lacks it
+static INDEX_FILE: &str = "backup.json";
+static ARCHIVE_PREFIX: &str = "ankisyncd-";
+/// sqlite sidecars,their content is part of the snapshot of the db itself
+static SQLITE_SIDECARS: [&str; 3] = ["-wal", "-shm", "-journal"];
+static SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// when to back up and how many archives to keep,stored in deployment.json
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupPolicy {
+    /// hours between scheduled backups,0 turns them off
+    pub interval_hours: u32,
+    /// newest archives which are never deleted
+    pub keep_last: usize,
+    /// archives younger than this are kept too
+    pub keep_days: u32,
+}
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            interval_hours: 24,
+            keep_last: 7,
+            keep_days: 30,
+        }
+    }
+}
+/// one backup archive in the backups dir
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    pub path: PathBuf,
+    pub created: OffsetDateTime,
+    pub size: u64,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Index {
+    #[serde(with = "time::serde::rfc3339")]
+    created: OffsetDateTime,
+    files: Vec<IndexEntry>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    /// relative to the data dir,always with / separators
+    path: String,
+    size: u64,
+}
+
+/// ankisyncd-20261018T153000Z.zip,UTC so the order survives time zone changes
+fn stamp_format() -> Vec<BorrowedFormatItem<'static>> {
+    time::format_description::parse_borrowed::<1>("[year][month][day]T[hour][minute][second]Z")
+        .expect("valid format description")
+}
+pub(crate) fn stamp(t: OffsetDateTime) -> String {
+    t.format(&stamp_format()).expect("utc time formats")
+}
+pub(crate) fn parse_stamp(stamp: &str) -> Option<OffsetDateTime> {
+    PrimitiveDateTime::parse(stamp, &stamp_format())
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+}
+fn parse_archive_name(path: &Path) -> Option<OffsetDateTime> {
+    let name = path.file_name()?.to_str()?;
+    parse_stamp(name.strip_prefix(ARCHIVE_PREFIX)?.strip_suffix(".zip")?)
+}
+
+/// archives in dir,newest first
+pub fn list(dir: &Path) -> Result<Vec<Archive>, ApplicationError> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut archives = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(created) = parse_archive_name(&path) {
+            let size = fs::metadata(&path)?.len();
+            archives.push(Archive {
+                path,
+                created,
+                size,
+            });
+        }
+    }
+    archives.sort_by_key(|a| std::cmp::Reverse(a.created));
+    Ok(archives)
+}
+
+/// snapshot collections and the dbs of data_dir into a new archive in backups_dir
+///
+/// sqlite files go through sqlite's online backup,ankisyncd may keep running
+pub fn create(data_dir: &Path, backups_dir: &Path) -> Result<Archive, ApplicationError> {
+    fs::create_dir_all(backups_dir)?;
+    // the name keeps whole seconds only,list must report the same time
+    let created = OffsetDateTime::now_utc()
+        .replace_nanosecond(0)
+        .expect("0 is a valid nanosecond");
+    let path = backups_dir.join(format!("{}{}.zip", ARCHIVE_PREFIX, stamp(created)));
+    let tmp = path.with_extension("zip.tmp");
+    let snapshot = backups_dir.join(".snapshot.tmp");
+
+    let result = write_archive(data_dir, &tmp, &snapshot, created);
+    let _ = fs::remove_file(&snapshot);
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, &path)?;
+    let size = fs::metadata(&path)?.len();
+    Ok(Archive {
+        path,
+        created,
+        size,
+    })
+}
+fn write_archive(
+    data_dir: &Path,
+    tmp: &Path,
+    snapshot: &Path,
+    created: OffsetDateTime,
+) -> Result<(), ApplicationError> {
+    let mut zip = ZipWriter::new(File::create(tmp)?);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    let mut files = Vec::new();
+    for rel in data_files(data_dir)? {
+        let src = data_dir.join(&rel);
+        let name = zip_name(&rel);
+        let mut file = if is_sqlite(&src)? {
+            sqlite_snapshot(&src, snapshot)?;
+            File::open(snapshot)?
+        } else {
+            File::open(&src)?
+        };
+        zip.start_file(name.as_str(), options)?;
+        let size = io::copy(&mut file, &mut zip)?;
+        files.push(IndexEntry { path: name, size });
+    }
+    zip.start_file(INDEX_FILE, options)?;
+    serde_json::to_writer_pretty(&mut zip, &Index { created, files })?;
+    zip.finish()?.sync_all()?;
+    Ok(())
+}
+/// collections/** and top level *.db,relative to data_dir
+fn data_files(data_dir: &Path) -> Result<Vec<PathBuf>, ApplicationError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(data_dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "db") {
+            files.push(PathBuf::from(path.file_name().unwrap_or_default()));
+        }
+    }
+    let mut dirs = vec![PathBuf::from(COLLECTIONS_DIR)];
+    while let Some(dir) = dirs.pop() {
+        let abs = data_dir.join(&dir);
+        if !abs.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(abs)? {
+            let entry = entry?;
+            let rel = dir.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                dirs.push(rel);
+            } else if !is_sidecar(&rel) {
+                files.push(rel);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+fn is_sidecar(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    SQLITE_SIDECARS.iter().any(|s| name.ends_with(s))
+}
+fn is_sqlite(path: &Path) -> Result<bool, ApplicationError> {
+    let mut magic = [0u8; 16];
+    let n = File::open(path)?.read(&mut magic)?;
+    Ok(n == magic.len() && &magic == SQLITE_MAGIC)
+}
+fn sqlite_snapshot(src: &Path, dst: &Path) -> Result<(), ApplicationError> {
+    let _ = fs::remove_file(dst);
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    // ankisyncd may hold a write lock for a moment
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.backup(DatabaseName::Main, dst, None)?;
+    Ok(())
+}
+fn zip_name(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// archives to delete: neither among the keep_last newest nor younger than keep_days
+///
+/// archives is newest first,the newest one is never deleted
+pub fn expired<'a>(
+    archives: &'a [Archive],
+    policy: &BackupPolicy,
+    now: OffsetDateTime,
+) -> Vec<&'a Archive> {
+    let cutoff = now - Duration::days(i64::from(policy.keep_days));
+    archives
+        .iter()
+        .enumerate()
+        .filter(|(i, a)| *i > 0 && *i >= policy.keep_last && a.created < cutoff)
+        .map(|(_, a)| a)
+        .collect()
+}
+/// delete expired archives,return their paths
+pub fn prune(dir: &Path, policy: &BackupPolicy) -> Result<Vec<PathBuf>, ApplicationError> {
+    let archives = list(dir)?;
+    let mut removed = Vec::new();
+    for archive in expired(&archives, policy, OffsetDateTime::now_utc()) {
+        fs::remove_file(&archive.path)?;
+        removed.push(archive.path.clone());
+    }
+    Ok(removed)
+}
+
+/// read every entry,zip checks its crc,and compare against the index
+fn verify(archive: &Path) -> Result<Index, ApplicationError> {
+    let corrupt = |why: String| ApplicationError::BackupCorrupt(archive.into(), why);
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+    let index: Index = match zip.by_name(INDEX_FILE) {
+        Ok(f) => serde_json::from_reader(f).map_err(|e| corrupt(e.to_string()))?,
+        Err(_) => return Err(corrupt(format!("{} missing", INDEX_FILE))),
+    };
+    for entry in &index.files {
+        let mut f = zip
+            .by_name(&entry.path)
+            .map_err(|_| corrupt(format!("{} missing", entry.path)))?;
+        let size = io::copy(&mut f, &mut io::sink()).map_err(|e| corrupt(e.to_string()))?;
+        if size != entry.size {
+            return Err(corrupt(format!("{} has {} bytes", entry.path, size)));
+        }
+    }
+    Ok(index)
+}
+/// swap the data in archive into data_dir,ankisyncd must be stopped
+///
+/// the replaced data is moved to data_dir/pre-restore-<time>,which is returned
+pub fn restore(archive: &Path, data_dir: &Path) -> Result<PathBuf, ApplicationError> {
+    let index = verify(archive)?;
+    let staging = data_dir.join(".restore.tmp");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    let mut zip = ZipArchive::new(File::open(archive)?)?;
+    let mut tops = BTreeSet::new();
+    for entry in &index.files {
+        let rel = safe_path(&entry.path).ok_or_else(|| {
+            ApplicationError::BackupCorrupt(archive.into(), format!("bad path {}", entry.path))
+        })?;
+        let dst = staging.join(&rel);
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut zip.by_name(&entry.path)?, &mut File::create(&dst)?)?;
+        if let Some(Component::Normal(top)) = rel.components().next() {
+            tops.insert(PathBuf::from(top));
+        }
+    }
+
+    let saved = data_dir.join(format!("pre-restore-{}", stamp(OffsetDateTime::now_utc())));
+    fs::create_dir_all(&saved)?;
+    for top in &tops {
+        // a stale -wal next to a restored db would be replayed into it
+        let mut current = vec![top.clone()];
+        current.extend(
+            SQLITE_SIDECARS
+                .iter()
+                .map(|s| PathBuf::from(format!("{}{}", top.display(), s))),
+        );
+        for old in current {
+            if data_dir.join(&old).exists() {
+                fs::rename(data_dir.join(&old), saved.join(&old))?;
+            }
+        }
+        fs::rename(staging.join(top), data_dir.join(top))?;
+    }
+    fs::remove_dir_all(&staging)?;
+    Ok(saved)
+}
+/// relative path without .. or a root,zip entries may be crafted
+fn safe_path(name: &str) -> Option<PathBuf> {
+    let path: PathBuf = name.split('/').collect();
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(days_ago: i64, now: OffsetDateTime) -> Archive {
+        let created = now - Duration::days(days_ago);
+        Archive {
+            path: format!("{}{}.zip", ARCHIVE_PREFIX, stamp(created)).into(),
+            created,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_newest_and_recent() {
+        let now = OffsetDateTime::now_utc();
+        let archives: Vec<Archive> = [0, 1, 2, 40, 50].iter().map(|d| archive(*d, now)).collect();
+        assert_eq!(
+            parse_archive_name(&archives[3].path).map(|t| t.unix_timestamp()),
+            Some(archives[3].created.unix_timestamp())
+        );
+        let policy = BackupPolicy {
+            interval_hours: 24,
+            keep_last: 2,
+            keep_days: 30,
+        };
+        let old: Vec<i64> = expired(&archives, &policy, now)
+            .iter()
+            .map(|a| (now - a.created).whole_days())
+            .collect();
+        assert_eq!(old, vec![40, 50]);
+        // the last archive survives even when everything is old
+        let strict = BackupPolicy {
+            keep_last: 0,
+            keep_days: 0,
+            ..policy
+        };
+        assert_eq!(expired(&archives, &strict, now).len(), 4);
+    }
+
+    #[test]
+    fn backup_and_restore_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("deployer-backup-{}", std::process::id()));
+        let data = dir.join("data");
+        fs::create_dir_all(data.join("collections").join("bob")).unwrap();
+        let db = Connection::open(data.join("auth.db")).unwrap();
+        db.execute_batch("CREATE TABLE auth (username TEXT); INSERT INTO auth VALUES ('bob');")
+            .unwrap();
+        fs::write(data.join("collections").join("bob").join("media.txt"), "v1").unwrap();
+        fs::write(data.join("ankisyncd"), "not data").unwrap();
+
+        let backups = dir.join("backups");
+        let made = create(&data, &backups).unwrap();
+        assert_eq!(list(&backups).unwrap(), vec![made.clone()]);
+
+        db.execute("DELETE FROM auth", []).unwrap();
+        drop(db);
+        fs::write(data.join("collections").join("bob").join("media.txt"), "v2").unwrap();
+        let saved = restore(&made.path, &data).unwrap();
+
+        let db = Connection::open(data.join("auth.db")).unwrap();
+        let users: i64 = db
+            .query_row("SELECT count(*) FROM auth", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(users, 1);
+        let media = data.join("collections").join("bob").join("media.txt");
+        assert_eq!(fs::read_to_string(media).unwrap(), "v1");
+        let replaced = saved.join("collections").join("bob").join("media.txt");
+        assert_eq!(fs::read_to_string(replaced).unwrap(), "v2");
+
+        // flip a byte in the middle,crc or deflate must catch it
+        let mut bytes = fs::read(&made.path).unwrap();
+        let mid = bytes.len() / 3;
+        bytes[mid] ^= 0xff;
+        fs::write(&made.path, bytes).unwrap();
+        assert!(restore(&made.path, &data).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
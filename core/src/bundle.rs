@@ -0,0 +1,141 @@
+use crate::addon_config::SyncAddr;
+use crate::diagnose::Credentials;
+use crate::error::ApplicationError;
+use crate::i18n::{lang, tr, trf, Lang};
+use crate::qr::phone_urls;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// name of the bundle dir,and of the zip without extension
+pub static BUNDLE_NAME: &str = "anki-sync-client";
+/// the name phones and Windows recognize as a cert
+static CA_FILE: &str = "rootCA.crt";
+static PAGE_FILE: &str = "index.html";
+
+/// what a phone or another PC needs to sync with ankisyncd
+#[derive(Debug, Clone)]
+pub struct ClientBundle {
+    pub addr: SyncAddr,
+    /// the root CA's cert in PEM
+    pub ca_cert: Vec<u8>,
+    /// account to log in with,its password ends up in plain text
+    pub account: Option<Credentials>,
+}
+impl ClientBundle {
+    /// file names and contents
+    pub fn files(&self) -> Result<Vec<(&'static str, Vec<u8>)>, ApplicationError> {
+        Ok(vec![
+            (CA_FILE, self.ca_cert.clone()),
+            (PAGE_FILE, self.page()?.into_bytes()),
+        ])
+    }
+    /// write the files into dir,creating it
+    pub fn write_dir(&self, dir: &Path) -> Result<PathBuf, ApplicationError> {
+        std::fs::create_dir_all(dir)?;
+        for (name, contents) in self.files()? {
+            std::fs::write(dir.join(name), contents)?;
+        }
+        Ok(dir.into())
+    }
+    /// write the files into a zip at path,inside a dir named after it
+    pub fn write_zip(&self, path: &Path) -> Result<PathBuf, ApplicationError> {
+        let folder = path
+            .file_stem()
+            .map_or(BUNDLE_NAME.into(), |s| s.to_string_lossy());
+        // written next to path first,a failed export leaves no half zip behind
+        let tmp = path.with_extension("zip.tmp");
+        let mut zip = ZipWriter::new(std::fs::File::create(&tmp)?);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, contents) in self.files()? {
+            zip.start_file(format!("{}/{}", folder, name), options)?;
+            zip.write_all(&contents)?;
+        }
+        zip.finish()?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(path.into())
+    }
+    /// index.html,urls with QR codes and setup steps per platform
+    fn page(&self) -> Result<String, ApplicationError> {
+        let mut urls = String::new();
+        for (label, url) in phone_urls(&self.addr) {
+            let _ = writeln!(
+                urls,
+                "<div class=\"url\"><h3>{}</h3><code>{}</code>{}</div>",
+                label,
+                escape(&url),
+                qr_svg(&url)?
+            );
+        }
+        let account = match &self.account {
+            Some(a) => trf(
+                "client_page.account",
+                &[&escape(&a.username), &escape(&a.password)],
+            ),
+            None => tr("client_page.no_account").into(),
+        };
+        // account last,a password may look like a placeholder
+        Ok(page_template(lang())
+            .replace("{sync_url}", &escape(&self.addr.sync_url()))
+            .replace("{urls}", &urls)
+            .replace("{account}", &account))
+    }
+}
+
+/// index.html with {urls},{account} and {sync_url} to fill in
+fn page_template(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Zh => include_str!("../locales/client_page.zh.html"),
+        Lang::En => include_str!("../locales/client_page.en.html"),
+    }
+}
+/// QR code of data as inline svg
+fn qr_svg(data: &str) -> Result<String, ApplicationError> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| ApplicationError::Qr(e.to_string()))?;
+    let svg = code.render::<svg::Color>().min_dimensions(200, 200).build();
+    // html takes the svg element without the xml declaration
+    Ok(svg[svg.find("<svg").unwrap_or(0)..].to_owned())
+}
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addon_config::Scheme;
+
+    #[test]
+    fn zip_holds_cert_and_page() {
+        let bundle = ClientBundle {
+            addr: SyncAddr::new(Scheme::Https, "192.168.1.2", 27701),
+            ca_cert: b"-----BEGIN CERTIFICATE-----".to_vec(),
+            account: Some(Credentials {
+                username: "me".into(),
+                password: "a<b".into(),
+            }),
+        };
+        let dir = std::env::temp_dir().join(format!("deploy-core-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = bundle.write_zip(&dir.join("client.zip")).unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+        let mut page = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("client/index.html").unwrap(), &mut page)
+            .unwrap();
+        assert!(page.contains("https://192.168.1.2:27701/msync"));
+        assert_eq!(page.matches("<svg").count(), 2);
+        assert!(!page.contains("<?xml"));
+        assert!(page.contains("a&lt;b"));
+        assert!(zip.by_name("client/rootCA.crt").is_ok());
+        assert!(!dir.join("client.zip.tmp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::ApplicationError;
+use crate::safe_write::{restrict_to_owner, write_atomic, write_private};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use time::{Duration, OffsetDateTime};
+
+/// days the root CA stays valid
+pub static CA_VALIDITY_DAYS: i64 = 3650;
+/// days a server cert stays valid,iOS refuses anything above 825
+pub static SERVER_VALIDITY_DAYS: i64 = 825;
+
+/// common name of the root CA,trust stores list and delete it by this
+pub static CA_COMMON_NAME: &str = "anki sync server root CA";
+
+pub static ROOTCA_FILE: &str = "rootCA.pem";
+pub static ROOTCA_KEY_FILE: &str = "rootCA-key.pem";
+pub static SERVER_CERT_FILE: &str = "server.pem";
+pub static SERVER_KEY_FILE: &str = "server-key.pem";
+
+/// local root CA which signs ankisyncd's server certs
+///
+/// created once and kept as rootCA.pem/rootCA-key.pem in its dir
+pub struct CertAuthority {
+    cert: Certificate,
+    dir: PathBuf,
+}
+/// PEM encoded server cert and its private key
+pub struct ServerCert {
+    pub cert_pem: String,
+    pub key_pem: String,
+}
+
+impl CertAuthority {
+    /// load the CA from dir,create a new one valid for validity_days if absent
+    pub async fn load_or_create(
+        dir: &Path,
+        validity_days: i64,
+    ) -> Result<CertAuthority, ApplicationError> {
+        let cert_path = dir.join(ROOTCA_FILE);
+        let key_path = dir.join(ROOTCA_KEY_FILE);
+        let cert = if cert_path.exists() && key_path.exists() {
+            // deployers before 0600 keys left it readable by everyone
+            restrict_to_owner(&key_path)?;
+            let cert_pem = async_std::fs::read_to_string(&cert_path).await?;
+            let key_pem = async_std::fs::read_to_string(&key_path).await?;
+            let key = KeyPair::from_pem(&key_pem)?;
+            Certificate::from_params(CertificateParams::from_ca_cert_pem(&cert_pem, key)?)?
+        } else {
+            let cert = Certificate::from_params(ca_params(validity_days))?;
+            async_std::fs::create_dir_all(dir).await?;
+            write_atomic(&cert_path, cert.serialize_pem()?.as_bytes())?;
+            write_private(&key_path, cert.serialize_private_key_pem().as_bytes())?;
+            cert
+        };
+        Ok(CertAuthority {
+            cert,
+            dir: dir.into(),
+        })
+    }
+    /// rootCA.pem which phones and trust stores import
+    pub fn cert_path(&self) -> PathBuf {
+        self.dir.join(ROOTCA_FILE)
+    }
+    /// issue a cert for localhost,127.0.0.1,::1 and ipaddr
+    pub fn issue_server_cert(
+        &self,
+        ipaddr: &str,
+        validity_days: i64,
+    ) -> Result<ServerCert, ApplicationError> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "anki sync server");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, format!("anki sync server {}", ipaddr));
+        params.subject_alt_names = ["localhost", "127.0.0.1", "::1", ipaddr]
+            .iter()
+            .map(|name| san(name))
+            .collect();
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        set_validity(&mut params, validity_days);
+
+        let cert = Certificate::from_params(params)?;
+        Ok(ServerCert {
+            cert_pem: cert.serialize_pem_with_signer(&self.cert)?,
+            key_pem: cert.serialize_private_key_pem(),
+        })
+    }
+}
+
+impl ServerCert {
+    /// write server.pem and server-key.pem into dir
+    ///
+    /// return their file names
+    pub async fn write(&self, dir: &Path) -> Result<(String, String), ApplicationError> {
+        // a half written pair would keep ankisyncd from starting
+        write_atomic(&dir.join(SERVER_CERT_FILE), self.cert_pem.as_bytes())?;
+        write_private(&dir.join(SERVER_KEY_FILE), self.key_pem.as_bytes())?;
+        Ok((SERVER_CERT_FILE.into(), SERVER_KEY_FILE.into()))
+    }
+}
+
+fn ca_params(validity_days: i64) -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::OrganizationName, "anki sync server");
+    params
+        .distinguished_name
+        .push(DnType::CommonName, CA_COMMON_NAME);
+    params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    set_validity(&mut params, validity_days);
+    params
+}
+/// start a day early so clients with a skewed clock accept it
+fn set_validity(params: &mut CertificateParams, validity_days: i64) {
+    let now = OffsetDateTime::now_utc();
+    params.not_before = now - Duration::days(1);
+    params.not_after = now + Duration::days(validity_days);
+}
+fn san(name: &str) -> SanType {
+    match name.parse::<IpAddr>() {
+        Ok(ip) => SanType::IpAddress(ip),
+        Err(_) => SanType::DnsName(name.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_keeps_ca_and_issues() {
+        let dir = std::env::temp_dir().join(format!("deployer-ca-{}", std::process::id()));
+        async_std::task::block_on(async {
+            let ca = CertAuthority::load_or_create(&dir, CA_VALIDITY_DAYS)
+                .await
+                .unwrap();
+            let first = std::fs::read_to_string(ca.cert_path()).unwrap();
+            let ca = CertAuthority::load_or_create(&dir, CA_VALIDITY_DAYS)
+                .await
+                .unwrap();
+            assert_eq!(first, std::fs::read_to_string(ca.cert_path()).unwrap());
+
+            let server = ca
+                .issue_server_cert("192.168.1.2", SERVER_VALIDITY_DAYS)
+                .unwrap();
+            assert!(server.cert_pem.starts_with("-----BEGIN CERTIFICATE-----"));
+            assert!(server.key_pem.contains("PRIVATE KEY"));
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keys_only_readable_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("deployer-ca-keys-{}", std::process::id()));
+        let mode = |p: PathBuf| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        async_std::task::block_on(async {
+            let ca = CertAuthority::load_or_create(&dir, CA_VALIDITY_DAYS)
+                .await
+                .unwrap();
+            assert_eq!(mode(dir.join(ROOTCA_KEY_FILE)), 0o600);
+            ca.issue_server_cert("192.168.1.2", SERVER_VALIDITY_DAYS)
+                .unwrap()
+                .write(&dir)
+                .await
+                .unwrap();
+            assert_eq!(mode(dir.join(SERVER_KEY_FILE)), 0o600);
+
+            let loose = std::fs::Permissions::from_mode(0o644);
+            std::fs::set_permissions(dir.join(ROOTCA_KEY_FILE), loose).unwrap();
+            CertAuthority::load_or_create(&dir, CA_VALIDITY_DAYS)
+                .await
+                .unwrap();
+            assert_eq!(mode(dir.join(ROOTCA_KEY_FILE)), 0o600);
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
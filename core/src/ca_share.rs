@@ -0,0 +1,109 @@
+use crate::addon_config::{Scheme, SyncAddr};
+use crate::error::ApplicationError;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::prelude::*;
+use async_std::{future, task};
+use std::path::Path;
+use std::time::Duration;
+
+/// port phones download rootCA.crt from,next to ankisyncd's
+pub static ROOTCA_PORT: u16 = 27702;
+static ROOTCA_URL_PATH: &str = "/rootCA.crt";
+/// a client that hasn't been served by then is dropped
+static CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// http://192.168.1.2:27702/rootCA.crt
+pub fn rootca_url(ipaddr: &str) -> String {
+    let addr = SyncAddr::new(Scheme::Http, ipaddr, ROOTCA_PORT);
+    format!("{}{}", addr.base_url(), ROOTCA_URL_PATH)
+}
+
+/// serve ca_cert at rootca_url until the future is dropped
+///
+/// plain http,phones can't verify https before the CA is installed
+pub async fn serve_rootca(ipaddr: &str, ca_cert: &Path) -> Result<(), ApplicationError> {
+    let listener = TcpListener::bind((ipaddr, ROOTCA_PORT)).await?;
+    serve(listener, ca_cert).await
+}
+async fn serve(listener: TcpListener, ca_cert: &Path) -> Result<(), ApplicationError> {
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        // one bad or silent client must not stop the others
+        if let Ok(stream) = stream {
+            let ca_cert = ca_cert.to_path_buf();
+            task::spawn(async move {
+                let _ = future::timeout(CLIENT_TIMEOUT, respond(stream, &ca_cert)).await;
+            });
+        }
+    }
+    Ok(())
+}
+async fn respond(mut stream: TcpStream, ca_cert: &Path) -> Result<(), ApplicationError> {
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let wanted = request.starts_with(&format!("GET {} ", ROOTCA_URL_PATH));
+    let response = if wanted {
+        let body = async_std::fs::read(ca_cert).await?;
+        // this content type makes Android and iOS offer to install the cert
+        let mut head = format!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/x-x509-ca-cert\r\n\
+             Content-Disposition: attachment; filename=\"rootCA.crt\"\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        head.extend(body);
+        head
+    } else {
+        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+    };
+    stream.write_all(&response).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_brackets_ipv6() {
+        assert_eq!(
+            rootca_url("192.168.1.2"),
+            "http://192.168.1.2:27702/rootCA.crt"
+        );
+        assert_eq!(rootca_url("fd00::2"), "http://[fd00::2]:27702/rootCA.crt");
+    }
+
+    #[test]
+    fn silent_client_does_not_block_others() {
+        let dir = std::env::temp_dir().join(format!("deployer-share-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ca_cert = dir.join("rootCA.crt");
+        std::fs::write(&ca_cert, "CERT").unwrap();
+        task::block_on(async {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = task::spawn(async move { serve(listener, &ca_cert).await });
+            // connects and never sends a request
+            let _silent = TcpStream::connect(addr).await.unwrap();
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client
+                .write_all(b"GET /rootCA.crt HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            future::timeout(Duration::from_secs(5), client.read_to_string(&mut response))
+                .await
+                .expect("second client is served")
+                .unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert!(response.ends_with("CERT"));
+            server.cancel().await;
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
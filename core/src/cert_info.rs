@@ -0,0 +1,108 @@
+use crate::error::ApplicationError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use time::OffsetDateTime;
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::parse_x509_pem;
+
+/// warn this many days before the server cert expires
+pub static RENEW_BEFORE_DAYS: i64 = 30;
+
+/// expiry and subject alt names of ankisyncd's server cert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    pub not_after: OffsetDateTime,
+    /// dns names and ip addresses the cert is valid for
+    pub sans: Vec<String>,
+}
+impl CertInfo {
+    pub async fn load(path: &Path) -> Result<CertInfo, ApplicationError> {
+        let pem = async_std::fs::read(path).await?;
+        Self::from_pem(&pem)
+    }
+    pub fn from_pem(pem: &[u8]) -> Result<CertInfo, ApplicationError> {
+        let (_, pem) =
+            parse_x509_pem(pem).map_err(|e| ApplicationError::CertParse(e.to_string()))?;
+        let cert = pem
+            .parse_x509()
+            .map_err(|e| ApplicationError::CertParse(e.to_string()))?;
+        let sans = match cert
+            .subject_alternative_name()
+            .map_err(|e| ApplicationError::CertParse(e.to_string()))?
+        {
+            Some(ext) => ext
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(ip) => ip_from_octets(ip).map(|ip| ip.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(CertInfo {
+            not_after: cert.validity().not_after.to_datetime(),
+            sans,
+        })
+    }
+    /// 2027-01-31
+    pub fn expiry_date(&self) -> String {
+        let d = self.not_after.date();
+        format!("{}-{:02}-{:02}", d.year(), d.month() as u8, d.day())
+    }
+    /// negative once expired
+    pub fn days_left(&self) -> i64 {
+        (self.not_after - OffsetDateTime::now_utc()).whole_days()
+    }
+    /// whether ipaddr is one of the cert's subject alt names
+    pub fn covers(&self, ipaddr: &str) -> bool {
+        match ipaddr.parse::<IpAddr>() {
+            Ok(ip) => self.sans.iter().any(|san| san.parse::<IpAddr>() == Ok(ip)),
+            Err(_) => self.sans.iter().any(|san| san.eq_ignore_ascii_case(ipaddr)),
+        }
+    }
+    /// expiring soon or not valid for ipaddr
+    pub fn needs_renewal(&self, ipaddr: &str) -> bool {
+        self.days_left() < RENEW_BEFORE_DAYS || !self.covers(ipaddr)
+    }
+}
+fn ip_from_octets(octets: &[u8]) -> Option<IpAddr> {
+    match octets.len() {
+        4 => <[u8; 4]>::try_from(octets)
+            .ok()
+            .map(Ipv4Addr::from)
+            .map(IpAddr::V4),
+        16 => <[u8; 16]>::try_from(octets)
+            .ok()
+            .map(Ipv6Addr::from)
+            .map(IpAddr::V6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_sans_and_expiry() {
+        let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]);
+        params
+            .subject_alt_names
+            .push(rcgen::SanType::IpAddress("192.168.1.2".parse().unwrap()));
+        params.not_after = rcgen::date_time_ymd(2030, 6, 1);
+        let pem = rcgen::Certificate::from_params(params)
+            .unwrap()
+            .serialize_pem()
+            .unwrap();
+
+        let info = CertInfo::from_pem(pem.as_bytes()).unwrap();
+        assert_eq!(info.sans, vec!["localhost", "192.168.1.2"]);
+        assert_eq!(info.expiry_date(), "2030-06-01");
+        assert!(info.covers("192.168.1.2"));
+        assert!(!info.covers("192.168.1.3"));
+        assert!(info.needs_renewal("10.0.0.1"));
+    }
+}
@@ -0,0 +1,511 @@
+use crate::addon_config::{Scheme, SyncAddr};
+use async_std::future::timeout;
+use async_std::io::{ReadExt, WriteExt};
+use async_std::net::TcpStream;
+use futures_rustls::client::TlsStream;
+use futures_rustls::rustls::{
+    self, Certificate, CertificateError, ClientConfig, RootCertStore, ServerName,
+};
+use futures_rustls::TlsConnector;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// per connect and per request
+static TIMEOUT: Duration = Duration::from_secs(5);
+/// boundary PC Anki uses for its sync requests
+static BOUNDARY: &str = "Anki-sync-boundary";
+/// client version sent to media begin,ankisyncd only logs it
+static CLIENT_VERSION: &str = "ankidesktop,2.1.35,deployer:diagnose";
+
+/// sync endpoint as a client sees it
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub ip: String,
+    pub port: u16,
+    /// rootCA.pem the server cert must chain to
+    pub ca_cert: PathBuf,
+}
+/// account hostKey logs in with
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+/// checks in the order they run,each needs the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Port,
+    Tls,
+    HostKey,
+    MediaBegin,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Failed(Problem),
+    /// an earlier check failed or no test account was given
+    Skipped,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: Check,
+    pub outcome: Outcome,
+}
+/// what went wrong,worded so the user knows what to do
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    #[error("nothing listens on {0},start ankisyncd or allow the port in the firewall")]
+    PortClosed(String),
+    #[error("no answer from {0},check the firewall and that the ip belongs to this machine")]
+    Timeout(String),
+    #[error("can't connect to {0}: {1}")]
+    Network(String, String),
+    #[error("root CA {0} not readable,run import-ca first")]
+    RootCaMissing(String),
+    #[error("server cert doesn't include {0},reissue it with install-certs")]
+    SanMismatch(String),
+    #[error("server cert isn't signed by the deployer's root CA,reissue it with install-certs")]
+    UnknownIssuer,
+    #[error("server cert expired or not valid yet,reissue it or fix the clock")]
+    CertExpired,
+    #[error("tls handshake failed: {0},check ssl_enable in Settings.toml")]
+    Tls(String),
+    #[error("username or password rejected,check the test account")]
+    AuthFailed,
+    #[error("server answered http {0}")]
+    Http(u16),
+    #[error("unexpected response: {0}")]
+    Protocol(String),
+}
+impl Check {
+    pub fn all() -> [Check; 4] {
+        [Check::Port, Check::Tls, Check::HostKey, Check::MediaBegin]
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Check::Port => "port",
+            Check::Tls => "tls",
+            Check::HostKey => "hostKey",
+            Check::MediaBegin => "media begin",
+        }
+    }
+}
+
+/// run every check against target,stop at the first failure
+///
+/// hostKey and media begin need credentials and are skipped without
+pub async fn run(target: &Target, credentials: Option<&Credentials>) -> Vec<CheckResult> {
+    let mut outcomes = Vec::new();
+    let result = checks(target, credentials, &mut outcomes).await;
+    if let Err(problem) = result {
+        outcomes.push(Outcome::Failed(problem));
+    }
+    outcomes.resize(Check::all().len(), Outcome::Skipped);
+    Check::all()
+        .iter()
+        .zip(outcomes)
+        .map(|(check, outcome)| CheckResult {
+            check: *check,
+            outcome,
+        })
+        .collect()
+}
+async fn checks(
+    target: &Target,
+    credentials: Option<&Credentials>,
+    outcomes: &mut Vec<Outcome>,
+) -> Result<(), Problem> {
+    tcp_connect(target).await?;
+    outcomes.push(Outcome::Ok);
+
+    let connector = connector(target).await?;
+    tls_connect(target, &connector).await?;
+    outcomes.push(Outcome::Ok);
+
+    let credentials = match credentials {
+        Some(c) => c,
+        None => return Ok(()),
+    };
+    let login = format!(
+        r#"{{"u":{},"p":{}}}"#,
+        serde_json::Value::from(credentials.username.as_str()),
+        serde_json::Value::from(credentials.password.as_str())
+    );
+    let (status, body) = post(
+        target,
+        &connector,
+        "/sync/hostKey",
+        &[("c", b"0"), ("data", login.as_bytes())],
+    )
+    .await?;
+    let hkey = match status {
+        200 => json_str(&body, &["key"])?,
+        403 => return Err(Problem::AuthFailed),
+        _ => return Err(Problem::Http(status)),
+    };
+    outcomes.push(Outcome::Ok);
+
+    let (status, body) = post(
+        target,
+        &connector,
+        "/msync/begin",
+        &[
+            ("k", hkey.as_bytes()),
+            ("v", CLIENT_VERSION.as_bytes()),
+            ("data", b"{}"),
+        ],
+    )
+    .await?;
+    match status {
+        200 => {
+            let err = json_str(&body, &["err"]).unwrap_or_default();
+            if !err.is_empty() {
+                return Err(Problem::Protocol(err));
+            }
+            json_str(&body, &["data", "sk"])?;
+        }
+        403 => return Err(Problem::AuthFailed),
+        _ => return Err(Problem::Http(status)),
+    }
+    outcomes.push(Outcome::Ok);
+    Ok(())
+}
+
+fn addr(target: &Target) -> String {
+    SyncAddr::new(Scheme::Https, &target.ip, target.port).base_url()
+}
+async fn tcp_connect(target: &Target) -> Result<TcpStream, Problem> {
+    let addr = addr(target);
+    match timeout(
+        TIMEOUT,
+        TcpStream::connect((target.ip.as_str(), target.port)),
+    )
+    .await
+    {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => Err(Problem::PortClosed(addr)),
+        Ok(Err(e)) => Err(Problem::Network(addr, e.to_string())),
+        Err(_) => Err(Problem::Timeout(addr)),
+    }
+}
+/// tls client trusting only the deployer's root CA
+async fn connector(target: &Target) -> Result<TlsConnector, Problem> {
+    let missing = || Problem::RootCaMissing(target.ca_cert.display().to_string());
+    let pem = async_std::fs::read(&target.ca_cert)
+        .await
+        .map_err(|_| missing())?;
+    let mut roots = RootCertStore::empty();
+    for der in rustls_pemfile::certs(&mut pem.as_slice()).map_err(|_| missing())? {
+        roots.add(&Certificate(der)).map_err(|_| missing())?;
+    }
+    if roots.is_empty() {
+        return Err(missing());
+    }
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+async fn tls_connect(
+    target: &Target,
+    connector: &TlsConnector,
+) -> Result<TlsStream<TcpStream>, Problem> {
+    let stream = tcp_connect(target).await?;
+    // an ip parses as ServerName::IpAddress and is matched against ip SANs
+    let name = ServerName::try_from(target.ip.as_str())
+        .map_err(|_| Problem::SanMismatch(target.ip.clone()))?;
+    match timeout(TIMEOUT, connector.connect(name, stream)).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) => Err(tls_problem(target, e)),
+        Err(_) => Err(Problem::Timeout(addr(target))),
+    }
+}
+fn tls_problem(target: &Target, e: std::io::Error) -> Problem {
+    let tls_error = e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>());
+    match tls_error {
+        Some(rustls::Error::InvalidCertificate(cert_error)) => match cert_error {
+            CertificateError::NotValidForName => Problem::SanMismatch(target.ip.clone()),
+            CertificateError::UnknownIssuer | CertificateError::BadSignature => {
+                Problem::UnknownIssuer
+            }
+            CertificateError::Expired | CertificateError::NotValidYet => Problem::CertExpired,
+            other => Problem::Tls(format!("{:?}", other)),
+        },
+        Some(other) => Problem::Tls(other.to_string()),
+        // plain http answers with garbage instead of a handshake
+        None => Problem::Tls(e.to_string()),
+    }
+}
+/// multipart form as PC Anki posts it,return status and body
+async fn post(
+    target: &Target,
+    connector: &TlsConnector,
+    path: &str,
+    fields: &[(&str, &[u8])],
+) -> Result<(u16, Vec<u8>), Problem> {
+    let mut stream = tls_connect(target, connector).await?;
+    let body = multipart(fields);
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: multipart/form-data; boundary={}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        path,
+        addr(target).trim_start_matches("https://"),
+        BOUNDARY,
+        body.len()
+    )
+    .into_bytes();
+    request.extend(body);
+    let exchange = async {
+        stream.write_all(&request).await?;
+        stream.flush().await?;
+        read_response(&mut stream).await
+    };
+    match timeout(TIMEOUT, exchange).await {
+        Ok(Ok(response)) => parse_response(&response),
+        Ok(Err(e)) => Err(Problem::Network(addr(target), e.to_string())),
+        Err(_) => Err(Problem::Timeout(addr(target))),
+    }
+}
+fn multipart(fields: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend(format!("--{}\r\n", BOUNDARY).into_bytes());
+        if *name == "data" {
+            body.extend(
+                b"Content-Disposition: form-data; name=\"data\"; filename=\"data\"\r\n\
+                  Content-Type: application/octet-stream\r\n\r\n",
+            );
+        } else {
+            body.extend(
+                format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).into_bytes(),
+            );
+        }
+        body.extend_from_slice(value);
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{}--\r\n", BOUNDARY).into_bytes());
+    body
+}
+/// read until the server closes,tolerating a missing close_notify
+async fn read_response(stream: &mut TlsStream<TcpStream>) -> std::io::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) => return Ok(response),
+            Ok(n) => response.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(response),
+            Err(e) => return Err(e),
+        }
+    }
+}
+fn parse_response(response: &[u8]) -> Result<(u16, Vec<u8>), Problem> {
+    let split = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| Problem::Protocol("no http response".into()))?;
+    let head = String::from_utf8_lossy(&response[..split]);
+    let body = &response[split + 4..];
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Problem::Protocol(head.lines().next().unwrap_or_default().into()))?;
+    let chunked = head.lines().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.starts_with("transfer-encoding:") && l.contains("chunked")
+    });
+    Ok((
+        status,
+        if chunked {
+            dechunk(body)
+        } else {
+            body.to_vec()
+        },
+    ))
+}
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while let Some(eol) = body.windows(2).position(|w| w == b"\r\n") {
+        let size = String::from_utf8_lossy(&body[..eol]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16);
+        match size {
+            Ok(size) if size > 0 && body.len() >= eol + 2 + size => {
+                out.extend_from_slice(&body[eol + 2..eol + 2 + size]);
+                body = &body[(eol + 4 + size).min(body.len())..];
+            }
+            _ => break,
+        }
+    }
+    out
+}
+/// string at path in a json body
+fn json_str(body: &[u8], path: &[&str]) -> Result<String, Problem> {
+    let value: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|_| Problem::Protocol(String::from_utf8_lossy(body).into_owned()))?;
+    path.iter()
+        .try_fold(&value, |v, key| v.get(key))
+        .and_then(|v| v.as_str())
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| Problem::Protocol(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::{CertAuthority, ROOTCA_FILE};
+    use async_std::net::TcpListener;
+    use async_std::task;
+    use futures_rustls::rustls::{PrivateKey, ServerConfig};
+    use futures_rustls::{server, TlsAcceptor};
+    use std::path::Path;
+
+    /// ankisyncd stand-in,bob/pw is the only account
+    async fn stub_server(ca_dir: &Path) -> u16 {
+        let ca = CertAuthority::load_or_create(ca_dir, 1).await.unwrap();
+        let cert = ca.issue_server_cert("192.168.1.2", 1).unwrap();
+        let certs = rustls_pemfile::certs(&mut cert.cert_pem.as_bytes()).unwrap();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut cert.key_pem.as_bytes()).unwrap();
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                certs.into_iter().map(Certificate).collect(),
+                PrivateKey(key[0].clone()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        task::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                task::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let _ = answer(&mut stream).await;
+                    }
+                });
+            }
+        });
+        port
+    }
+    async fn answer(stream: &mut server::TlsStream<TcpStream>) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // one request per connection,read until Content-Length is satisfied
+        loop {
+            let n = stream.read(&mut buf).await?;
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some(split) = text.find("\r\n\r\n") {
+                let length: usize = text
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Content-Length: "))
+                    .and_then(|l| l.parse().ok())
+                    .unwrap_or(0);
+                if request.len() >= split + 4 + length || n == 0 {
+                    break;
+                }
+            }
+        }
+        let request = String::from_utf8_lossy(&request);
+        let (status, body) = if request.starts_with("POST /sync/hostKey ") {
+            if request.contains(r#"{"u":"bob","p":"pw"}"#) {
+                ("200 OK", r#"{"key":"hk1"}"#)
+            } else {
+                ("403 Forbidden", "")
+            }
+        } else if request.starts_with("POST /msync/begin ") && request.contains("hk1") {
+            ("200 OK", r#"{"data":{"sk":"sk1","usn":0},"err":""}"#)
+        } else {
+            ("404 Not Found", "")
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.get_mut().1.send_close_notify();
+        stream.flush().await
+    }
+    fn outcomes(results: &[CheckResult]) -> Vec<Outcome> {
+        results.iter().map(|r| r.outcome.clone()).collect()
+    }
+
+    #[test]
+    fn checks_against_stub_server() {
+        let dir = std::env::temp_dir().join(format!("deployer-diag-{}", std::process::id()));
+        task::block_on(async {
+            let port = stub_server(&dir.join("ca")).await;
+            let target = Target {
+                ip: "127.0.0.1".into(),
+                port,
+                ca_cert: dir.join("ca").join(ROOTCA_FILE),
+            };
+            let bob = |password: &str| Credentials {
+                username: "bob".into(),
+                password: password.into(),
+            };
+            let all_ok = run(&target, Some(&bob("pw"))).await;
+            assert_eq!(outcomes(&all_ok), vec![Outcome::Ok; 4]);
+
+            let no_account = run(&target, None).await;
+            assert_eq!(
+                outcomes(&no_account),
+                vec![Outcome::Ok, Outcome::Ok, Outcome::Skipped, Outcome::Skipped]
+            );
+
+            let wrong = run(&target, Some(&bob("nope"))).await;
+            assert_eq!(wrong[2].outcome, Outcome::Failed(Problem::AuthFailed));
+            assert_eq!(wrong[3].outcome, Outcome::Skipped);
+
+            // a CA the stub's cert wasn't issued by
+            CertAuthority::load_or_create(&dir.join("other"), 1)
+                .await
+                .unwrap();
+            let other_ca = Target {
+                ca_cert: dir.join("other").join(ROOTCA_FILE),
+                ..target.clone()
+            };
+            let untrusted = run(&other_ca, None).await;
+            assert_eq!(untrusted[0].outcome, Outcome::Ok);
+            assert_eq!(
+                untrusted[1].outcome,
+                Outcome::Failed(Problem::UnknownIssuer)
+            );
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn closed_port() {
+        // bind and drop to get a port nothing listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let target = Target {
+            ip: "127.0.0.1".into(),
+            port,
+            ca_cert: PathBuf::from("missing.pem"),
+        };
+        let results = task::block_on(run(&target, None));
+        assert_eq!(
+            results[0].outcome,
+            Outcome::Failed(Problem::PortClosed(format!("https://127.0.0.1:{}", port)))
+        );
+        assert!(results[1..].iter().all(|r| r.outcome == Outcome::Skipped));
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// every failure of a deployment operation,front ends show its message as is
+#[derive(Error, Debug)]
+pub enum ApplicationError {
+    #[error("IO error {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Parse Int error {0}")]
+    ParseInt(#[from] std::num::ParseIntError),
+    #[error("from utf8 error {0}")]
+    FromUtf8(#[from] std::string::FromUtf8Error),
+    #[error("environment variable error {0}")]
+    Env(#[from] std::env::VarError),
+    #[error("JSON error {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("TOML error {0}")]
+    Toml(#[from] toml_edit::TomlError),
+    #[error("PC Anki not found")]
+    AnkiNotFound,
+    #[error("can't parse Anki version {0}")]
+    AnkiVersion(String),
+    #[error("{0:?} not found,open PC Anki once first")]
+    NoAnkiProfile(PathBuf),
+    #[error("Anki profile error {0}")]
+    AnkiProfile(String),
+    #[error("pickle error {0}")]
+    Pickle(#[from] serde_pickle::Error),
+    #[error("{0:?} is not a JSON object")]
+    ConfigNotObject(PathBuf),
+    #[error("{0:?} has no sync address with host and port")]
+    NoSyncAddr(PathBuf),
+    #[error("certificate error {0}")]
+    Cert(#[from] rcgen::RcgenError),
+    #[error("can't parse certificate {0}")]
+    CertParse(String),
+    #[error("cert file not found: {0:?}")]
+    CertFileMissing(PathBuf),
+    #[error("key file not found: {0:?}")]
+    KeyFileMissing(PathBuf),
+    #[error("QR code error {0}")]
+    Qr(String),
+    #[error("no LAN address found,is the network cable plugged in?")]
+    NoLanAddr,
+    #[error("{0} failed: {1}")]
+    CommandFailed(String, String),
+    #[error("deployment manifest version {0} is newer than this deployer")]
+    ManifestVersion(u32),
+    #[error("auth db error {0}")]
+    AuthDb(#[from] rusqlite::Error),
+    #[error("auth db is locked,stop ankisyncd and retry")]
+    AuthDbLocked,
+    #[error("user {0} already exists")]
+    UserExists(String),
+    #[error("user {0} not found")]
+    UserNotFound(String),
+    #[error("invalid port {0}")]
+    InvalidPort(String),
+    #[error("port {0} is in use by {1}")]
+    PortInUse(u16, String),
+    #[error("zip error {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("backup {0:?} is damaged: {1}")]
+    BackupCorrupt(PathBuf, String),
+    #[error("ankisyncd is still running,restore needs it stopped")]
+    ServerBusy,
+    #[error("{0:?} is not a backup of a config file")]
+    NotConfigBackup(PathBuf),
+    #[error("unknown data store error")]
+    Unknown,
+}
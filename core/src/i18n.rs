@@ -0,0 +1,161 @@
+use crate::platform::Platform;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use toml_edit::{Document, Item, Table};
+
+/// languages with a catalog in core/locales
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+impl Lang {
+    pub fn all() -> [Lang; 2] {
+        [Lang::Zh, Lang::En]
+    }
+    /// code kept in deployment.json
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::En => "en",
+        }
+    }
+    pub fn from_code(code: &str) -> Option<Lang> {
+        Lang::all().into_iter().find(|l| l.code() == code)
+    }
+    /// name in the language itself,for the picker
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Lang::Zh => "中文",
+            Lang::En => "English",
+        }
+    }
+    /// zh_CN.UTF-8,zh-Hans-CN,en-US and the like,None without a catalog
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let primary = locale.split(['_', '-', '.', '@']).next()?;
+        Lang::from_code(&primary.to_ascii_lowercase())
+    }
+    /// text needs glyphs the default font lacks
+    pub fn needs_cjk(&self) -> bool {
+        matches!(self, Lang::Zh)
+    }
+    fn catalog_src(&self) -> &'static str {
+        match self {
+            Lang::Zh => include_str!("../locales/zh.toml"),
+            Lang::En => include_str!("../locales/en.toml"),
+        }
+    }
+}
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.native_name())
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn lang() -> Lang {
+    Lang::all()[CURRENT.load(Ordering::Relaxed) as usize]
+}
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+/// use the language chosen in the wizard,else the system locale's
+///
+/// Chinese when the locale can't be read,English when it has no catalog
+pub fn init(platform: &dyn Platform, chosen: Option<&str>) -> Lang {
+    let lang = chosen
+        .and_then(Lang::from_code)
+        .unwrap_or_else(|| match platform.locale() {
+            Some(locale) => Lang::from_locale(&locale).unwrap_or(Lang::En),
+            None => Lang::Zh,
+        });
+    set_lang(lang);
+    lang
+}
+
+fn catalog(lang: Lang) -> &'static HashMap<String, String> {
+    static LOADED: [OnceLock<HashMap<String, String>>; 2] = [OnceLock::new(), OnceLock::new()];
+    LOADED[lang as usize].get_or_init(|| {
+        let mut messages = HashMap::new();
+        // catalogs are compiled in,the tests catch a broken one
+        if let Ok(doc) = lang.catalog_src().parse::<Document>() {
+            flatten("", doc.as_table(), &mut messages);
+        }
+        messages
+    })
+}
+/// [section] key = "..." becomes section.key
+fn flatten(prefix: &str, table: &Table, out: &mut HashMap<String, String>) {
+    for (key, item) in table.iter() {
+        let key = if prefix.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match item {
+            Item::Table(t) => flatten(&key, t, out),
+            Item::Value(v) => {
+                if let Some(s) = v.as_str() {
+                    out.insert(key, s.to_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+/// message key in the current language,falling back to Chinese and then the key itself
+pub fn tr(key: &'static str) -> &'static str {
+    catalog(lang())
+        .get(key)
+        .or_else(|| catalog(Lang::Zh).get(key))
+        .map_or(key, String::as_str)
+}
+/// tr with {0},{1}.. replaced by args
+pub fn trf(key: &'static str, args: &[&dyn fmt::Display]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(tr(key).to_owned(), |msg, (i, arg)| {
+            msg.replace(&format!("{{{}}}", i), &arg.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_match() {
+        let placeholders = |s: &str| (0..4).filter(|i| s.contains(&format!("{{{}}}", i))).count();
+        let zh = catalog(Lang::Zh);
+        let en = catalog(Lang::En);
+        assert!(zh.len() > 100);
+        let mut keys: Vec<&String> = zh.keys().chain(en.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let (z, e) = match (zh.get(key), en.get(key)) {
+                (Some(z), Some(e)) => (z, e),
+                _ => panic!("{} is missing from a catalog", key),
+            };
+            assert_eq!(placeholders(z), placeholders(e), "{}", key);
+        }
+    }
+
+    #[test]
+    fn picks_language_from_locale() {
+        assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Some(Lang::Zh));
+        assert_eq!(Lang::from_locale("zh-Hans-CN"), Some(Lang::Zh));
+        assert_eq!(Lang::from_locale("en_US"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("de_DE.UTF-8"), None);
+        assert_eq!(Lang::from_code("en"), Some(Lang::En));
+
+        set_lang(Lang::En);
+        assert_eq!(trf("server.running", &[&42]), "Running (PID 42)");
+        assert_eq!(tr("no.such.key"), "no.such.key");
+        set_lang(Lang::Zh);
+        assert_eq!(tr("step.welcome"), "欢迎");
+    }
+}
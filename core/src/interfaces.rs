@@ -0,0 +1,187 @@
+use crate::error::ApplicationError;
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+
+/// name prefixes of adapters phones can't reach,docker,VMs and VPNs
+static VIRTUAL_PREFIXES: [&str; 12] = [
+    "docker",
+    "veth",
+    "br-",
+    "virbr",
+    "vmnet",
+    "vboxnet",
+    "vethernet",
+    "virtualbox",
+    "vmware",
+    "tun",
+    "tap",
+    "wg",
+];
+
+/// one address of a network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanAddr {
+    /// interface name,eth0 or 以太网
+    pub name: String,
+    pub ip: IpAddr,
+    pub flags: AddrFlags,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AddrFlags {
+    pub ipv6: bool,
+    /// 192.168/16,10/8,172.16/12 or IPv6 unique local fc00::/7
+    pub private: bool,
+    /// 169.254/16 or fe80::/10,only reachable on the same link
+    pub link_local: bool,
+    /// docker,VM or VPN adapter going by its name
+    pub virtual_adapter: bool,
+}
+impl AddrFlags {
+    pub fn of(name: &str, ip: &IpAddr) -> AddrFlags {
+        let lower = name.to_lowercase();
+        let virtual_adapter = VIRTUAL_PREFIXES.iter().any(|p| lower.starts_with(p));
+        match ip {
+            IpAddr::V4(v4) => AddrFlags {
+                ipv6: false,
+                private: v4.is_private(),
+                link_local: v4.is_link_local(),
+                virtual_adapter,
+            },
+            IpAddr::V6(v6) => AddrFlags {
+                ipv6: true,
+                private: is_unique_local(v6),
+                link_local: is_unicast_link_local(v6),
+                virtual_adapter,
+            },
+        }
+    }
+    /// short labels for the set flags
+    pub fn labels(&self) -> Vec<&'static str> {
+        [
+            (self.ipv6, "ipv6"),
+            (self.private, "private"),
+            (self.link_local, "link-local"),
+            (self.virtual_adapter, "virtual"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, label)| *label)
+        .collect()
+    }
+}
+impl fmt::Display for LanAddr {
+    /// eth0 192.168.1.2 [private]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.ip)?;
+        let labels = self.flags.labels();
+        if !labels.is_empty() {
+            write!(f, " [{}]", labels.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// every non-loopback address,likeliest LAN address first
+///
+/// private IPv4 on a physical adapter,then other IPv4,then IPv6,
+/// link-local and virtual adapters last
+pub fn lan_addrs() -> Result<Vec<LanAddr>, ApplicationError> {
+    let addrs = if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|i| !i.is_loopback())
+        .map(|i| {
+            let ip = i.ip();
+            LanAddr {
+                flags: AddrFlags::of(&i.name, &ip),
+                name: i.name,
+                ip,
+            }
+        })
+        .collect();
+    Ok(sorted(addrs))
+}
+fn sorted(mut addrs: Vec<LanAddr>) -> Vec<LanAddr> {
+    addrs.sort_by_key(rank);
+    addrs.dedup();
+    addrs
+}
+fn rank(a: &LanAddr) -> (bool, bool, bool, bool, String, IpAddr) {
+    (
+        a.flags.virtual_adapter,
+        a.flags.link_local,
+        a.flags.ipv6,
+        !a.flags.private,
+        a.name.clone(),
+        a.ip,
+    )
+}
+/// remembered interface if it still has an address,else the first one
+///
+/// remembered is an interface name or an ip
+pub fn preferred<'a>(addrs: &'a [LanAddr], remembered: Option<&str>) -> Option<&'a LanAddr> {
+    remembered
+        .and_then(|r| {
+            addrs
+                .iter()
+                .find(|a| a.name == r && !a.flags.ipv6)
+                .or_else(|| addrs.iter().find(|a| a.name == r))
+                .or_else(|| addrs.iter().find(|a| a.ip.to_string() == r))
+        })
+        .or_else(|| addrs.first())
+}
+fn is_unique_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+fn is_unicast_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(name: &str, ip: &str) -> LanAddr {
+        let ip = ip.parse().unwrap();
+        LanAddr {
+            name: name.into(),
+            flags: AddrFlags::of(name, &ip),
+            ip,
+        }
+    }
+
+    #[test]
+    fn physical_private_ipv4_first() {
+        let addrs = sorted(vec![
+            addr("docker0", "172.17.0.1"),
+            addr("eth0", "fd00::2"),
+            addr("eth0", "fe80::1"),
+            addr("wlan0", "100.64.0.3"),
+            addr("eth0", "192.168.1.2"),
+        ]);
+        let order: Vec<String> = addrs.iter().map(|a| a.ip.to_string()).collect();
+        assert_eq!(
+            order,
+            vec![
+                "192.168.1.2",
+                "100.64.0.3",
+                "fd00::2",
+                "fe80::1",
+                "172.17.0.1"
+            ]
+        );
+        assert_eq!(addrs[0].to_string(), "eth0 192.168.1.2 [private]");
+        assert_eq!(addrs[2].flags.labels(), vec!["ipv6", "private"]);
+    }
+
+    #[test]
+    fn remembered_choice_wins_while_present() {
+        let addrs = sorted(vec![addr("eth0", "192.168.1.2"), addr("wlan0", "10.0.0.5")]);
+        assert_eq!(
+            preferred(&addrs, Some("wlan0")).unwrap().ip.to_string(),
+            "10.0.0.5"
+        );
+        assert_eq!(preferred(&addrs, Some("10.0.0.5")).unwrap().name, "wlan0");
+        assert_eq!(preferred(&addrs, Some("usb0")).unwrap().name, "eth0");
+        assert!(preferred(&[], None).is_none());
+    }
+}
@@ -0,0 +1,41 @@
+//! everything the deployer and updateaddr do to a machine,without any UI
+//!
+//! every deployment operation is an async fn in [logic] returning
+//! [error::ApplicationError],the deployer wizard,its CLI and updateaddr
+//! only collect input and show results
+//!
+//! - PC Anki: [logic::anki_version],[logic::sync_target]
+//! - set ip: [logic::pcip_modify],[logic::set_port],[logic::lookup_ip]
+//! - certificates: [logic::import_to_sysstore],[logic::install_servcerts],[logic::renew_servcerts]
+//! - launchers: [logic::send_shortcut],[logic::add_startmenu]
+//! - dry run: [logic::deploy_plan] lists what the steps above would change
+//! - accounts: [logic::addusr],[logic::lsusr],[logic::delusr],[logic::chgepass]
+//! - clients: [logic::export_bundle]
+//! - server: [supervisor::supervisor],[logic::diagnose],[logic::addr_watch]
+//! - data: [logic::backup_now],[logic::restore_backup],[rollback::uninstall]
+//! - config files: written through [safe_write::safe_write],[logic::list_config_backups],[logic::restore_config_backup]
+pub mod accounts;
+pub mod addon_config;
+pub mod anki;
+pub mod backup;
+pub mod bundle;
+pub mod ca;
+pub mod ca_share;
+pub mod cert_info;
+pub mod diagnose;
+mod diff;
+pub mod error;
+pub mod i18n;
+pub mod interfaces;
+pub mod logic;
+pub mod manifest;
+pub mod plan;
+pub mod platform;
+pub mod qr;
+pub mod rollback;
+pub mod root;
+pub mod safe_write;
+pub mod settings;
+pub mod step_log;
+pub mod supervisor;
+pub mod watch;
@@ -0,0 +1,649 @@
+use crate::accounts::{Account, AccountStore};
+use crate::addon_config::{AddonConfig, Scheme, SyncAddr, DEFAULT_SYNC_PORT};
+use crate::anki::{AnkiVersion, Profiles, SyncTarget};
+use crate::backup::{self, Archive};
+use crate::bundle::{ClientBundle, BUNDLE_NAME};
+use crate::ca::{CertAuthority, CA_VALIDITY_DAYS, ROOTCA_FILE, SERVER_VALIDITY_DAYS};
+use crate::cert_info::CertInfo;
+use crate::diagnose::{self, CheckResult, Credentials, Target};
+use crate::error::ApplicationError;
+use crate::i18n::Lang;
+use crate::interfaces::{lan_addrs, preferred};
+use crate::manifest::{Manifest, StepKind};
+use crate::plan::{addon_ops, ca_ops, profile_ops, servcert_ops, ssl_ops, Operation, Plan};
+use crate::platform::Platform;
+use crate::root;
+use crate::safe_write::{self, FileBackup};
+use crate::settings::ServerSettings;
+use crate::step_log::{self, Source};
+use crate::supervisor::{supervisor, ServerState};
+use crate::watch::{Watch, WatchEvent};
+use async_std::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// pre/deployment.json,see manifest::Manifest
+pub fn manifest_path() -> PathBuf {
+    root_dir().join("deployment.json")
+}
+/// pre dir of the deployment root,always absolute,see root::DeploymentRoot
+pub fn root_dir() -> PathBuf {
+    root::current().pre()
+}
+/// dir holding the deployer's root CA
+pub fn ca_dir() -> PathBuf {
+    root_dir().join("ca")
+}
+pub static CONF_PATH: &str = r"Settings.toml";
+/// set for PC Anki when server certs are installed,it ships its own CA bundle
+pub static NOVERIFYSSL_ENV: &str = "ANKI_NOVERIFYSSL";
+
+/// copy of a config file as it was before the deployer first touched it
+pub fn orig_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".orig");
+    path.with_file_name(name)
+}
+/// keep the original of path once,later deploys must not overwrite it
+async fn backup_orig(path: &Path) -> Result<(), ApplicationError> {
+    let orig = orig_path(path);
+    if path.exists() && !orig.exists() {
+        async_std::fs::copy(path, orig).await?;
+    }
+    Ok(())
+}
+
+/// installed PC Anki,from the platform or else the release which last opened prefs21.db
+pub fn anki_version(platform: &dyn Platform) -> Result<AnkiVersion, ApplicationError> {
+    match platform.anki_version().and_then(|v| v.parse()) {
+        Ok(version) => Ok(version),
+        // e.g. a flatpak or portable Anki the platform doesn't see
+        Err(e) => Profiles::open(&Profiles::path_in(&platform.anki_profile_dir()?))
+            .ok()
+            .and_then(|p| p.last_run_version())
+            .ok_or(e),
+    }
+}
+
+/// if PC anki ver is above 2.1.10
+pub fn pc_ver_required(platform: &dyn Platform) -> bool {
+    matches!(anki_version(platform), Ok(v) if v.is_supported())
+}
+/// where PC Anki of the installed version takes its sync server from
+///
+/// 2.1.57 and later have the setting built in,older ones need the ankisyncd addon
+pub fn sync_target(platform: &dyn Platform) -> Result<SyncTarget, ApplicationError> {
+    if anki_version(platform)?.has_builtin_sync() {
+        let db = Profiles::path_in(&platform.anki_profile_dir()?);
+        Ok(SyncTarget::Profiles(db))
+    } else {
+        Ok(SyncTarget::Addon(platform.addon_dir()?.join("ankisyncd")))
+    }
+}
+/// deployer's copy of the ankisyncd addon in root,pre/addon/ankisyncd
+pub fn addon_src(root: &Path) -> PathBuf {
+    root.join("addon").join("ankisyncd")
+}
+/// copy addon files from src to PC Anki's ankisyncd_dir,creating it if needed
+///
+/// files already there are overwritten,a user's own config.json is kept as
+/// config.json.orig first
+pub async fn copy_addon(src: &Path, ankisyncd_dir: &Path) -> Result<(), ApplicationError> {
+    // a user installed addon gets its config back on uninstall
+    backup_orig(&AddonConfig::path_in(ankisyncd_dir)).await?;
+    async_std::fs::create_dir_all(ankisyncd_dir).await?;
+
+    let mut entries = async_std::fs::read_dir(src).await?;
+    while let Some(res) = entries.next().await {
+        let entry = res?.path();
+        if let Some(name) = entry.file_name() {
+            async_std::fs::copy(&entry, ankisyncd_dir.join(name)).await?;
+        }
+    }
+    Ok(())
+}
+/// point config.json in ankisyncd_dir at addr,return true if the address changed
+pub async fn set_pcip(ankisyncd_dir: &Path, addr: &SyncAddr) -> Result<bool, ApplicationError> {
+    SyncTarget::Addon(ankisyncd_dir.into()).set(addr).await
+}
+/// point PC Anki at ipaddr
+///
+/// Anki with a built-in sync server setting gets it set in every profile,
+/// older ones get the addon copied from the deployer dir with its sync address modified
+pub async fn pcip_modify(platform: &dyn Platform, ipaddr: &str) -> Result<(), ApplicationError> {
+    let mut inputs = vec![("ip".to_owned(), ipaddr.to_owned())];
+    let result: Result<(), ApplicationError> = async {
+        let target = sync_target(platform)?;
+        match &target {
+            SyncTarget::Addon(dir) => {
+                copy_addon(&addon_src(&root_dir()), dir).await?;
+                inputs.push(("mode".into(), "addon".into()));
+            }
+            SyncTarget::Profiles(db) => {
+                // uninstall gives every profile its own server back
+                let profiles = Profiles::open(db)?;
+                for name in profiles.names()? {
+                    let url = profiles.custom_sync_url(&name)?.unwrap_or_default();
+                    inputs.push((format!("previous:{}", name), url));
+                }
+                inputs.push(("mode".into(), "builtin".into()));
+            }
+        }
+        let addr = SyncAddr::new(Scheme::Https, ipaddr, sync_port().await?);
+        target.set(&addr).await.map(|_| ())
+    }
+    .await;
+    let inputs: Vec<(&str, &str)> = inputs
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    record_step(StepKind::PcipModified, &inputs, result).await
+}
+/// create root CA if absent and import it to system trusted store
+///
+///  send rootCA.crt to desktop
+pub async fn import_to_sysstore(platform: &dyn Platform) -> Result<(), ApplicationError> {
+    let result: Result<(), ApplicationError> = async {
+        let ca = CertAuthority::load_or_create(&ca_dir(), CA_VALIDITY_DAYS).await?;
+        platform.trust_ca(&ca.cert_path())?;
+
+        let rootca_desktop_path = platform.desktop()?.join("rootCA.crt");
+        async_std::fs::copy(ca.cert_path(), rootca_desktop_path).await?;
+        Ok(())
+    }
+    .await;
+    record_step(StepKind::CaImported, &[], result).await
+}
+/// issue server cert and key files signed by the root CA
+///
+/// write their paths to file Settings.toml
+pub async fn install_servcerts(
+    platform: &dyn Platform,
+    ipaddr: &str,
+) -> Result<(), ApplicationError> {
+    let result: Result<(), ApplicationError> = async {
+        let ca = CertAuthority::load_or_create(&ca_dir(), CA_VALIDITY_DAYS).await?;
+        let (cert, key) = ca
+            .issue_server_cert(ipaddr, SERVER_VALIDITY_DAYS)?
+            .write(&root_dir())
+            .await?;
+        // PC Anki ships its own CA bundle,tell it to skip verification
+        platform.set_user_env(NOVERIFYSSL_ENV, "1")?;
+
+        set_ssl(cert, key).await
+    }
+    .await;
+    record_step(StepKind::CertsIssued, &[("ip", ipaddr)], result).await
+}
+/// parse cert_file configured in Settings.toml
+pub async fn server_cert_info() -> Result<CertInfo, ApplicationError> {
+    let settings = ServerSettings::load(root_dir().join(CONF_PATH)).await?;
+    let cert = settings
+        .cert_file
+        .as_ref()
+        .ok_or_else(|| ApplicationError::CertFileMissing(PathBuf::new()))?;
+    CertInfo::load(&settings.resolve(cert)).await
+}
+/// reissue server cert for ipaddr,return the new cert's info
+pub async fn renew_servcerts(
+    platform: &dyn Platform,
+    ipaddr: String,
+) -> Result<CertInfo, ApplicationError> {
+    install_servcerts(platform, &ipaddr).await?;
+    server_cert_info().await
+}
+/// create shortcut to ankisyncd and send it to desktop
+pub async fn send_shortcut(platform: &dyn Platform) -> Result<(), ApplicationError> {
+    let result = launcher_target(platform)
+        .and_then(|target| platform.create_shortcut(&target, &platform.desktop()?));
+    let path = result
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    record_step(StepKind::ShortcutSent, &[("path", &path)], result)
+        .await
+        .map(|_| ())
+}
+/// every change steps would make when deploying on ipaddr,nothing is written
+///
+/// steps run in the order given,as the wizard and CLI would run them
+pub async fn deploy_plan(
+    platform: &dyn Platform,
+    steps: &[StepKind],
+    ipaddr: &str,
+) -> Result<Plan, ApplicationError> {
+    let mut plan = Plan::new(ipaddr);
+    for step in steps {
+        let ops = match step {
+            StepKind::PcipModified => {
+                let addr = SyncAddr::new(Scheme::Https, ipaddr, sync_port().await?);
+                match sync_target(platform)? {
+                    SyncTarget::Addon(dir) => {
+                        addon_ops(&addon_src(&root_dir()), &dir, &addr).await?
+                    }
+                    SyncTarget::Profiles(db) => profile_ops(&db, &addr)?,
+                }
+            }
+            StepKind::CaImported => {
+                let cert = ca_dir().join(ROOTCA_FILE);
+                let mut ops = ca_ops(&ca_dir());
+                ops.push(Operation::TrustCa { cert: cert.clone() });
+                ops.push(Operation::CopyFile {
+                    from: cert,
+                    to: platform.desktop()?.join("rootCA.crt"),
+                });
+                ops
+            }
+            StepKind::CertsIssued => {
+                let conf = root_dir().join(CONF_PATH);
+                let mut ops = ca_ops(&ca_dir());
+                ops.extend(servcert_ops(&conf));
+                ops.push(Operation::SetEnv {
+                    name: NOVERIFYSSL_ENV.into(),
+                    value: "1".into(),
+                });
+                ops.extend(ssl_ops(&conf).await?);
+                ops
+            }
+            StepKind::ShortcutSent => vec![Operation::CreateShortcut {
+                target: launcher_target(platform)?,
+                dir: platform.desktop()?,
+            }],
+            StepKind::StartMenuAdded => vec![Operation::CreateShortcut {
+                target: launcher_target(platform)?,
+                dir: platform.start_menu_dir()?,
+            }],
+        };
+        plan.push(*step, ops);
+    }
+    Ok(plan)
+}
+/// where the wizard exports a plan,deploy-plan.txt on the desktop
+pub fn plan_path(platform: &dyn Platform) -> Result<PathBuf, ApplicationError> {
+    Ok(platform.desktop()?.join("deploy-plan.txt"))
+}
+/// where the wizard saves the step log,deploy-log.txt on the desktop
+pub fn step_log_path(platform: &dyn Platform) -> Result<PathBuf, ApplicationError> {
+    Ok(platform.desktop()?.join("deploy-log.txt"))
+}
+/// run step as its own command does,ip is ignored by steps which don't take one
+pub async fn run_step(
+    platform: &dyn Platform,
+    step: StepKind,
+    ip: &str,
+) -> Result<(), ApplicationError> {
+    match step {
+        StepKind::PcipModified => pcip_modify(platform, ip).await,
+        StepKind::CaImported => import_to_sysstore(platform).await,
+        StepKind::CertsIssued => install_servcerts(platform, ip).await,
+        StepKind::ShortcutSent => send_shortcut(platform).await,
+        StepKind::StartMenuAdded => add_startmenu(platform).await,
+    }
+}
+/// append the outcome of step to the manifest,pass result through
+async fn record_step<T>(
+    step: StepKind,
+    inputs: &[(&str, &str)],
+    result: Result<T, ApplicationError>,
+) -> Result<T, ApplicationError> {
+    let error = result.as_ref().err().map(ToString::to_string);
+    let outcome = match &error {
+        None => "ok".to_owned(),
+        Some(e) => format!("failed: {}", e),
+    };
+    step_log::log(Source::Status, outcome);
+    let path = manifest_path();
+    let mut manifest = Manifest::load(&path).await?;
+    manifest.record(step, inputs, error);
+    manifest.save(&path).await?;
+    result
+}
+/// add anki_server launcher to start menu
+///
+/// Windows:C:\Users\Admin\AppData\Roaming\Microsoft\Windows\Start Menu\Programs
+/// Linux:~/.local/share/applications
+pub async fn add_startmenu(platform: &dyn Platform) -> Result<(), ApplicationError> {
+    let result = launcher_target(platform)
+        .and_then(|target| platform.create_shortcut(&target, &platform.start_menu_dir()?));
+    let path = result
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    record_step(StepKind::StartMenuAdded, &[("path", &path)], result)
+        .await
+        .map(|_| ())
+}
+/// ankisyncd in the deployer's pre dir
+fn launcher_target(platform: &dyn Platform) -> Result<PathBuf, ApplicationError> {
+    Ok(root_dir().join(platform.ankisyncd_exe()))
+}
+/// enable ssl in Settings.toml with cert and key file names in pre dir
+async fn set_ssl(cert: String, key: String) -> Result<(), ApplicationError> {
+    let path = root_dir().join(CONF_PATH);
+    backup_orig(&path).await?;
+    let mut settings = ServerSettings::load(path).await?;
+    settings.set_ssl(cert, key);
+    settings.save().await?;
+
+    Ok(())
+}
+/// LAN address to deploy on,the remembered interface if it's still up
+pub async fn lookup_ip() -> Result<String, ApplicationError> {
+    let remembered = Manifest::load(&manifest_path())
+        .await
+        .ok()
+        .and_then(|m| m.lan_choice);
+    let addrs = lan_addrs()?;
+    preferred(&addrs, remembered.as_deref())
+        .map(|a| a.ip.to_string())
+        .ok_or(ApplicationError::NoLanAddr)
+}
+/// remember the interface holding ip for the next lookup_ip
+pub async fn remember_ip(ip: String) -> Result<(), ApplicationError> {
+    let addrs = lan_addrs()?;
+    let name = match addrs.iter().find(|a| a.ip.to_string() == ip) {
+        Some(a) => a.name.clone(),
+        // not one of ours,e.g. a NAT address typed in by hand
+        None => return Ok(()),
+    };
+    let path = manifest_path();
+    let mut manifest = Manifest::load(&path).await?;
+    manifest.lan_choice = Some(name);
+    manifest.save(&path).await
+}
+/// keep the language picked in the wizard for the next start and updateaddr
+pub async fn remember_lang(lang: Lang) -> Result<(), ApplicationError> {
+    let path = manifest_path();
+    let mut manifest = Manifest::load(&path).await?;
+    manifest.lang = Some(lang.code().into());
+    manifest.save(&path).await
+}
+/// open the auth db configured in Settings.toml
+async fn account_store() -> Result<AccountStore, ApplicationError> {
+    let settings = ServerSettings::load(root_dir().join(CONF_PATH)).await?;
+    AccountStore::open(&settings.auth_db())
+}
+pub async fn lsusr() -> Result<Vec<Account>, ApplicationError> {
+    account_store().await?.list()
+}
+pub async fn delusr(name: &str) -> Result<(), ApplicationError> {
+    account_store().await?.delete(name)
+}
+pub async fn chgepass(name: &str, pass: &str) -> Result<(), ApplicationError> {
+    account_store().await?.set_password(name, pass)
+}
+pub async fn addusr(name: &str, pass: &str) -> Result<(), ApplicationError> {
+    account_store().await?.add(name, pass)
+}
+/// port ankisyncd listens on,the default before Settings.toml exists
+pub async fn sync_port() -> Result<u16, ApplicationError> {
+    let path = root_dir().join(CONF_PATH);
+    if !path.exists() {
+        return Ok(DEFAULT_SYNC_PORT);
+    }
+    Ok(ServerSettings::load(path).await?.port)
+}
+/// fail if something other than the supervised ankisyncd listens on port
+pub fn check_port(platform: &dyn Platform, port: u16) -> Result<(), ApplicationError> {
+    if port == 0 {
+        return Err(ApplicationError::InvalidPort(port.to_string()));
+    }
+    match std::net::TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            let owner = platform.port_owner(port);
+            let ours = match supervisor().status().state {
+                ServerState::Running { pid } => Some(pid),
+                _ => None,
+            };
+            match owner {
+                Some(owner) if Some(owner.pid) == ours => Ok(()),
+                Some(owner) => Err(ApplicationError::PortInUse(port, owner.to_string())),
+                None => Err(ApplicationError::PortInUse(port, "another process".into())),
+            }
+        }
+        // e.g. ports below 1024 need root on Linux
+        Err(e) => Err(e.into()),
+    }
+}
+/// move ankisyncd to port,in Settings.toml and wherever PC Anki keeps its sync address
+///
+/// a running ankisyncd keeps the old port until restarted
+pub async fn set_port(platform: &dyn Platform, port: u16) -> Result<(), ApplicationError> {
+    check_port(platform, port)?;
+    let mut settings = ServerSettings::load(root_dir().join(CONF_PATH)).await?;
+    settings.port = port;
+    settings.save().await?;
+
+    // set ip not run yet,it writes the port later
+    let target = match sync_target(platform) {
+        Ok(target) => target,
+        Err(_) => return Ok(()),
+    };
+    if let Some(mut addr) = target.current().await {
+        addr.port = port;
+        target.set(&addr).await?;
+    }
+    Ok(())
+}
+/// where export_bundle writes on the desktop by default
+pub fn bundle_path(platform: &dyn Platform, zip: bool) -> Result<PathBuf, ApplicationError> {
+    let desktop = platform.desktop()?;
+    Ok(if zip {
+        desktop.join(format!("{}.zip", BUNDLE_NAME))
+    } else {
+        desktop.join(BUNDLE_NAME)
+    })
+}
+/// write rootCA.crt and a page with the urls for ipaddr to out,a dir or with zip a zip file
+///
+/// account must exist in the auth db,its password can't be checked there
+pub async fn export_bundle(
+    ipaddr: &str,
+    out: PathBuf,
+    zip: bool,
+    account: Option<Credentials>,
+) -> Result<PathBuf, ApplicationError> {
+    let ca_path = ca_dir().join(ROOTCA_FILE);
+    if !ca_path.exists() {
+        return Err(ApplicationError::CertFileMissing(ca_path));
+    }
+    if let Some(account) = &account {
+        if !lsusr()
+            .await?
+            .iter()
+            .any(|a| a.username == account.username)
+        {
+            return Err(ApplicationError::UserNotFound(account.username.clone()));
+        }
+    }
+    let bundle = ClientBundle {
+        addr: SyncAddr::new(Scheme::Https, ipaddr, sync_port().await?),
+        ca_cert: async_std::fs::read(ca_path).await?,
+        account,
+    };
+    async_std::task::spawn_blocking(move || {
+        if zip {
+            bundle.write_zip(&out)
+        } else {
+            bundle.write_dir(&out)
+        }
+    })
+    .await
+}
+/// check a client can sync with ankisyncd at ipaddr,port from Settings.toml
+pub async fn diagnose(
+    ipaddr: String,
+    credentials: Option<Credentials>,
+) -> Result<Vec<CheckResult>, ApplicationError> {
+    let settings = ServerSettings::load(root_dir().join(CONF_PATH)).await?;
+    let target = Target {
+        ip: ipaddr,
+        port: settings.port,
+        ca_cert: ca_dir().join(ROOTCA_FILE),
+    };
+    Ok(diagnose::run(&target, credentials.as_ref()).await)
+}
+/// pre/backups,archives of ankisyncd's data
+pub fn backups_dir() -> PathBuf {
+    root_dir().join("backups")
+}
+/// dir ankisyncd keeps collections and dbs in,root_dir of Settings.toml
+async fn data_dir() -> Result<PathBuf, ApplicationError> {
+    let settings = ServerSettings::load(root_dir().join(CONF_PATH)).await?;
+    Ok(settings.resolve(&settings.root_dir))
+}
+pub async fn list_backups() -> Result<Vec<Archive>, ApplicationError> {
+    async_std::task::spawn_blocking(|| backup::list(&backups_dir())).await
+}
+/// archive ankisyncd's data now,then drop archives the policy no longer keeps
+pub async fn backup_now() -> Result<Archive, ApplicationError> {
+    let data_dir = data_dir().await?;
+    let policy = Manifest::load(&manifest_path()).await?.backup;
+    async_std::task::spawn_blocking(move || {
+        let archive = backup::create(&data_dir, &backups_dir())?;
+        backup::prune(&backups_dir(), &policy)?;
+        Ok(archive)
+    })
+    .await
+}
+/// back up if the newest archive is older than the policy's interval,None if not due
+pub async fn scheduled_backup() -> Option<Result<Archive, ApplicationError>> {
+    let policy = match Manifest::load(&manifest_path()).await {
+        Ok(manifest) => manifest.backup,
+        Err(e) => return Some(Err(e)),
+    };
+    if policy.interval_hours == 0 {
+        return None;
+    }
+    let newest = match list_backups().await {
+        Ok(archives) => archives.into_iter().next(),
+        Err(e) => return Some(Err(e)),
+    };
+    let interval = time::Duration::hours(i64::from(policy.interval_hours));
+    match newest {
+        Some(a) if time::OffsetDateTime::now_utc() - a.created < interval => None,
+        _ => Some(backup_now().await),
+    }
+}
+/// stop ankisyncd,swap archive's data in and start it again if it was running
+///
+/// return the dir the replaced data was moved to
+pub async fn restore_backup(
+    platform: &dyn Platform,
+    archive: PathBuf,
+) -> Result<PathBuf, ApplicationError> {
+    let server = supervisor();
+    let was_running = !matches!(
+        server.status().state,
+        ServerState::Stopped | ServerState::Failed(_)
+    );
+    server.stop_and_wait().await;
+    // an ankisyncd we don't supervise would keep the old dbs open
+    if let Err(ApplicationError::PortInUse(..)) = check_port(platform, sync_port().await?) {
+        return Err(ApplicationError::ServerBusy);
+    }
+    let data_dir = data_dir().await?;
+    let result =
+        async_std::task::spawn_blocking(move || backup::restore(&archive, &data_dir)).await;
+    if was_running {
+        server.start();
+    }
+    result
+}
+/// config files the deployer rewrites,Settings.toml and the addon's config.json on old Anki
+pub fn config_files(platform: &dyn Platform) -> Vec<PathBuf> {
+    let mut files = vec![root_dir().join(CONF_PATH)];
+    if let Ok(SyncTarget::Addon(dir)) = sync_target(platform) {
+        files.push(AddonConfig::path_in(&dir));
+    }
+    files
+}
+/// backups safe_write kept of the config files,newest first
+pub async fn list_config_backups(
+    platform: &dyn Platform,
+) -> Result<Vec<FileBackup>, ApplicationError> {
+    let mut found = Vec::new();
+    for file in config_files(platform) {
+        found.extend(safe_write::backups(&file)?);
+    }
+    found.sort_by_key(|b| std::cmp::Reverse(b.created));
+    Ok(found)
+}
+/// write backup over its config file,what it replaces becomes a backup in turn
+///
+/// return the restored file
+pub async fn restore_config_backup(backup: FileBackup) -> Result<PathBuf, ApplicationError> {
+    safe_write::restore(&backup)?;
+    Ok(backup.original)
+}
+/// watch for LAN address changes,following the interface picked on the set ip page
+pub async fn addr_watch(platform: &dyn Platform) -> Result<Watch, ApplicationError> {
+    let target = sync_target(platform)?;
+    let remembered = Manifest::load(&manifest_path()).await?.lan_choice;
+    let cert_file = match ServerSettings::load(root_dir().join(CONF_PATH)).await {
+        Ok(settings) => settings.cert_file.as_ref().map(|c| settings.resolve(c)),
+        Err(_) => None,
+    };
+    Ok(Watch::new(target, remembered, cert_file).await)
+}
+/// next address change,with the server cert reissued if it doesn't cover the new address
+pub async fn follow_addr(platform: &dyn Platform, watch: &mut Watch) -> Vec<WatchEvent> {
+    let mut events = watch.next().await;
+    let outdated: Vec<String> = events
+        .iter()
+        .filter_map(|e| match e {
+            WatchEvent::CertOutdated { ip } => Some(ip.clone()),
+            _ => None,
+        })
+        .collect();
+    for ip in outdated {
+        match install_servcerts(platform, &ip).await {
+            Ok(()) => {
+                // ankisyncd loads its cert on start only
+                if let ServerState::Running { .. } = supervisor().status().state {
+                    supervisor().restart();
+                }
+                events.push(WatchEvent::CertRenewed { ip });
+            }
+            Err(e) => events.push(WatchEvent::failed("cert", e)),
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_into_existing_addon_dir() {
+        async_std::task::block_on(async {
+            let dir =
+                std::env::temp_dir().join(format!("deploy-core-addon-{}", std::process::id()));
+            let src = addon_src(&dir.join("pre"));
+            let dst = dir.join("addons21").join("ankisyncd");
+            std::fs::create_dir_all(&src).unwrap();
+            std::fs::create_dir_all(&dst).unwrap();
+            std::fs::write(src.join("__init__.py"), "v2").unwrap();
+            std::fs::write(src.join("config.json"), r#"{"syncaddr": ""}"#).unwrap();
+            std::fs::write(dst.join("__init__.py"), "v1").unwrap();
+            std::fs::write(dst.join("config.json"), r#"{"syncaddr": "mine"}"#).unwrap();
+
+            // an addon already installed is updated,not skipped
+            copy_addon(&src, &dst).await.unwrap();
+            assert_eq!(
+                std::fs::read_to_string(dst.join("__init__.py")).unwrap(),
+                "v2"
+            );
+            let orig = orig_path(&AddonConfig::path_in(&dst));
+            assert!(std::fs::read_to_string(orig).unwrap().contains("mine"));
+
+            let addr = SyncAddr::new(Scheme::Https, "192.168.1.2", DEFAULT_SYNC_PORT);
+            assert!(set_pcip(&dst, &addr).await.unwrap());
+            assert!(!set_pcip(&dst, &addr).await.unwrap());
+            let conf = AddonConfig::load(AddonConfig::path_in(&dst)).await.unwrap();
+            assert_eq!(conf.parsed_sync_addr(), Some(addr));
+            std::fs::remove_dir_all(dir).unwrap();
+        });
+    }
+}
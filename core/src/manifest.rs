@@ -0,0 +1,209 @@
+use crate::backup::BackupPolicy;
+use crate::error::ApplicationError;
+use crate::safe_write::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// bump when the layout of deployment.json changes
+pub static MANIFEST_VERSION: u32 = 1;
+
+/// side-effecting deployment steps,in wizard order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    PcipModified,
+    CaImported,
+    CertsIssued,
+    ShortcutSent,
+    StartMenuAdded,
+}
+impl StepKind {
+    pub fn all() -> [StepKind; 5] {
+        [
+            StepKind::PcipModified,
+            StepKind::CaImported,
+            StepKind::CertsIssued,
+            StepKind::ShortcutSent,
+            StepKind::StartMenuAdded,
+        ]
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    Failed { error: String },
+}
+/// one run of a step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub step: StepKind,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    /// arguments the step ran with,e.g. ip
+    pub inputs: BTreeMap<String, String>,
+    pub outcome: Outcome,
+}
+/// pre/deployment.json,history of every deployment step on this machine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// finished deployments,one is counted when the last step succeeds
+    pub deploy_count: u32,
+    /// interface the user picked on the set ip page
+    #[serde(default)]
+    pub lan_choice: Option<String>,
+    /// language code picked in the wizard,the system locale's if None
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// scheduled backups of ankisyncd's data
+    #[serde(default)]
+    pub backup: BackupPolicy,
+    pub steps: Vec<StepRecord>,
+}
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            version: MANIFEST_VERSION,
+            deploy_count: 0,
+            lan_choice: None,
+            lang: None,
+            backup: BackupPolicy::default(),
+            steps: Vec::new(),
+        }
+    }
+}
+impl Manifest {
+    /// read manifest,fall back to the old deploy_count.txt next to it
+    pub async fn load(path: &Path) -> Result<Manifest, ApplicationError> {
+        if !path.exists() {
+            return Ok(Self::migrate(path).await);
+        }
+        let contents = async_std::fs::read_to_string(path).await?;
+        let manifest: Manifest = serde_json::from_str(&contents)?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(ApplicationError::ManifestVersion(manifest.version));
+        }
+        Ok(manifest)
+    }
+    /// deploy_count.txt only held a number,garbage counts as no deployment
+    async fn migrate(path: &Path) -> Manifest {
+        let old = path.with_file_name("deploy_count.txt");
+        let deploy_count = async_std::fs::read_to_string(old)
+            .await
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        Manifest {
+            deploy_count,
+            ..Manifest::default()
+        }
+    }
+    /// write to a temp file and rename over the manifest
+    ///
+    /// no backups,the deployer rewrites it after every step
+    pub async fn save(&self, path: &Path) -> Result<(), ApplicationError> {
+        write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())
+    }
+    pub fn record(&mut self, step: StepKind, inputs: &[(&str, &str)], error: Option<String>) {
+        let outcome = match error {
+            None => Outcome::Ok,
+            Some(error) => Outcome::Failed { error },
+        };
+        if step == StepKind::StartMenuAdded && outcome == Outcome::Ok {
+            self.deploy_count += 1;
+        }
+        self.steps.push(StepRecord {
+            step,
+            timestamp: OffsetDateTime::now_utc(),
+            inputs: inputs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            outcome,
+        });
+    }
+    /// latest run of step
+    pub fn last(&self, step: StepKind) -> Option<&StepRecord> {
+        self.steps.iter().rev().find(|r| r.step == step)
+    }
+    /// whether the latest run of step succeeded
+    pub fn is_done(&self, step: StepKind) -> bool {
+        self.last(step).is_some_and(|r| r.outcome == Outcome::Ok)
+    }
+    pub fn first_incomplete(&self) -> Option<StepKind> {
+        StepKind::all().into_iter().find(|s| !self.is_done(*s))
+    }
+    /// whether the latest run of step succeeded,for ip if the step took one
+    pub fn is_done_for(&self, step: StepKind, ip: &str) -> bool {
+        self.is_done(step)
+            && self
+                .last(step)
+                .and_then(|r| r.inputs.get("ip"))
+                .is_none_or(|done_ip| done_ip == ip)
+    }
+    /// drop step records and deploy count once everything was uninstalled
+    ///
+    /// language,lan choice and backup policy are the user's settings,they stay
+    pub fn forget_deployment(&mut self) {
+        self.steps.clear();
+        self.deploy_count = 0;
+    }
+    /// step a half finished deployment continues with,None when there's nothing to resume
+    pub fn resume_step(&self) -> Option<StepKind> {
+        self.first_incomplete().filter(|_| !self.steps.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_at_first_incomplete_step() {
+        let mut m = Manifest::default();
+        assert_eq!(m.resume_step(), None);
+        m.record(StepKind::PcipModified, &[("ip", "192.168.1.2")], None);
+        m.record(StepKind::CaImported, &[], None);
+        m.record(StepKind::CertsIssued, &[], Some("disk full".into()));
+        assert_eq!(m.first_incomplete(), Some(StepKind::CertsIssued));
+        assert_eq!(m.resume_step(), Some(StepKind::CertsIssued));
+        assert!(m.is_done_for(StepKind::PcipModified, "192.168.1.2"));
+        assert!(!m.is_done_for(StepKind::PcipModified, "10.0.0.2"));
+        assert!(m.is_done_for(StepKind::CaImported, "10.0.0.2"));
+
+        m.record(StepKind::CertsIssued, &[], None);
+        m.record(StepKind::ShortcutSent, &[], None);
+        assert_eq!(m.resume_step(), Some(StepKind::StartMenuAdded));
+        m.record(StepKind::StartMenuAdded, &[], None);
+        assert_eq!(m.deploy_count, 1);
+        assert_eq!(m.resume_step(), None);
+
+        let json = serde_json::to_string(&m).unwrap();
+        let back: Manifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            back.last(StepKind::PcipModified).unwrap().inputs["ip"],
+            "192.168.1.2"
+        );
+    }
+
+    #[test]
+    fn uninstall_keeps_user_settings() {
+        let mut m = Manifest {
+            lan_choice: Some("192.168.1.2".into()),
+            lang: Some("en".into()),
+            ..Manifest::default()
+        };
+        m.backup.keep_last = 3;
+        m.record(StepKind::ShortcutSent, &[], None);
+        m.record(StepKind::StartMenuAdded, &[], None);
+        m.forget_deployment();
+        assert!(m.steps.is_empty());
+        assert_eq!(m.deploy_count, 0);
+        assert_eq!(m.lan_choice.as_deref(), Some("192.168.1.2"));
+        assert_eq!(m.lang.as_deref(), Some("en"));
+        assert_eq!(m.backup.keep_last, 3);
+    }
+}
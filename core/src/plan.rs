@@ -0,0 +1,261 @@
+use crate::addon_config::{AddonConfig, SyncAddr};
+use crate::anki::Profiles;
+use crate::ca::{ROOTCA_FILE, ROOTCA_KEY_FILE, SERVER_CERT_FILE, SERVER_KEY_FILE};
+use crate::diff::unified;
+use crate::error::ApplicationError;
+use crate::logic::orig_path;
+use crate::manifest::StepKind;
+use crate::settings::ServerSettings;
+use async_std::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// one change a deployment step makes to the system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    /// copy a file,replacing to
+    CopyFile { from: PathBuf, to: PathBuf },
+    /// write a file the deployer generates,e.g. a cert
+    WriteFile { path: PathBuf },
+    /// rewrite a config file,diff is unified
+    EditFile { path: PathBuf, diff: String },
+    /// custom sync url of a profile in prefs21.db
+    SetProfileSyncUrl {
+        profile: String,
+        from: Option<String>,
+        to: String,
+    },
+    /// persist an environment variable for the current user
+    SetEnv { name: String, value: String },
+    /// add a root CA to the system trust store
+    TrustCa { cert: PathBuf },
+    /// anki_server shortcut to target in dir
+    CreateShortcut { target: PathBuf, dir: PathBuf },
+}
+impl Operation {
+    /// unified diff of an edit,empty for other operations
+    pub fn diff(&self) -> &str {
+        match self {
+            Operation::EditFile { diff, .. } => diff,
+            _ => "",
+        }
+    }
+}
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::CopyFile { from, to } => {
+                write!(f, "copy {} to {}", from.display(), to.display())
+            }
+            Operation::WriteFile { path } => write!(f, "write {}", path.display()),
+            Operation::EditFile { path, .. } => write!(f, "edit {}", path.display()),
+            Operation::SetProfileSyncUrl { profile, from, to } => write!(
+                f,
+                "set sync server of Anki profile {} from {} to {}",
+                profile,
+                from.as_deref().unwrap_or("AnkiWeb"),
+                to
+            ),
+            Operation::SetEnv { name, value } => write!(f, "set user env {}={}", name, value),
+            Operation::TrustCa { cert } => {
+                write!(f, "add {} to the system trust store", cert.display())
+            }
+            Operation::CreateShortcut { target, dir } => write!(
+                f,
+                "create anki_server shortcut to {} in {}",
+                target.display(),
+                dir.display()
+            ),
+        }
+    }
+}
+
+/// operations of one step,in the order they run
+#[derive(Debug, Clone, Serialize)]
+pub struct StepPlan {
+    pub step: StepKind,
+    pub operations: Vec<Operation>,
+}
+/// every change the chosen steps would make,built without touching anything
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub ip: String,
+    pub steps: Vec<StepPlan>,
+}
+impl Plan {
+    pub fn new(ip: &str) -> Plan {
+        Plan {
+            ip: ip.into(),
+            steps: Vec::new(),
+        }
+    }
+    /// add operations of step,leaving out those an earlier step makes already
+    pub fn push(&mut self, step: StepKind, operations: Vec<Operation>) {
+        let operations = operations
+            .into_iter()
+            .filter(|op| !self.operations().any(|(_, planned)| planned == op))
+            .collect();
+        self.steps.push(StepPlan { step, operations });
+    }
+    pub fn operations(&self) -> impl Iterator<Item = (StepKind, &Operation)> {
+        self.steps
+            .iter()
+            .flat_map(|s| s.operations.iter().map(move |op| (s.step, op)))
+    }
+    /// write the plan as text,as the CLI prints it
+    pub async fn save(&self, path: &Path) -> Result<(), ApplicationError> {
+        async_std::fs::write(path, self.to_string()).await?;
+        Ok(())
+    }
+}
+impl fmt::Display for Plan {
+    /// numbered operations per step,each edit followed by its diff
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "deployment plan for {}", self.ip)?;
+        let mut n = 0;
+        for step in &self.steps {
+            writeln!(f, "\n{:?}:", step.step)?;
+            if step.operations.is_empty() {
+                writeln!(f, "  nothing to change")?;
+            }
+            for op in &step.operations {
+                n += 1;
+                writeln!(f, "  {}. {}", n, op)?;
+                f.write_str(op.diff())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// copy_addon from src to dir,then point its config.json at addr
+///
+/// config.json is shown as one edit from what is there now to the result
+pub async fn addon_ops(
+    src: &Path,
+    dir: &Path,
+    addr: &SyncAddr,
+) -> Result<Vec<Operation>, ApplicationError> {
+    let conf_path = AddonConfig::path_in(dir);
+    let mut ops: Vec<Operation> = backup_orig_op(&conf_path).into_iter().collect();
+    let mut names = Vec::new();
+    let mut entries = async_std::fs::read_dir(src).await?;
+    while let Some(res) = entries.next().await {
+        names.push(res?.file_name());
+    }
+    names.sort();
+    let mut conf_src = conf_path.clone();
+    for name in names {
+        // the deployer's config.json replaces the user's,shown in the edit below
+        if Some(name.as_os_str()) == conf_path.file_name() {
+            conf_src = src.join(name);
+            continue;
+        }
+        ops.push(Operation::CopyFile {
+            from: src.join(&name),
+            to: dir.join(&name),
+        });
+    }
+    let old = async_std::fs::read_to_string(&conf_path)
+        .await
+        .unwrap_or_default();
+    let mut conf = AddonConfig::load(&conf_src).await?;
+    conf.set_sync_addr(addr);
+    ops.extend(edit_op(&conf_path, &old, &conf.to_json()?));
+    Ok(ops)
+}
+/// every profile in db pointed at addr
+pub fn profile_ops(db: &Path, addr: &SyncAddr) -> Result<Vec<Operation>, ApplicationError> {
+    let profiles = Profiles::open(db)?;
+    let url = addr.sync_url();
+    let mut ops = Vec::new();
+    for name in profiles.names()? {
+        let from = profiles.custom_sync_url(&name)?;
+        if from.as_deref() != Some(url.as_str()) {
+            ops.push(Operation::SetProfileSyncUrl {
+                profile: name,
+                from,
+                to: url.clone(),
+            });
+        }
+    }
+    Ok(ops)
+}
+/// the root CA's files,if it doesn't exist yet
+pub fn ca_ops(ca_dir: &Path) -> Vec<Operation> {
+    let files = [ca_dir.join(ROOTCA_FILE), ca_dir.join(ROOTCA_KEY_FILE)];
+    if files.iter().all(|f| f.exists()) {
+        return Vec::new();
+    }
+    files
+        .into_iter()
+        .map(|path| Operation::WriteFile { path })
+        .collect()
+}
+/// server cert and key written next to Settings.toml at conf
+pub fn servcert_ops(conf: &Path) -> Vec<Operation> {
+    let dir = conf.parent().unwrap_or_else(|| Path::new("."));
+    [SERVER_CERT_FILE, SERVER_KEY_FILE]
+        .into_iter()
+        .map(|name| Operation::WriteFile {
+            path: dir.join(name),
+        })
+        .collect()
+}
+/// ssl enabled with the server cert in Settings.toml at conf
+pub async fn ssl_ops(conf: &Path) -> Result<Vec<Operation>, ApplicationError> {
+    let mut ops: Vec<Operation> = backup_orig_op(conf).into_iter().collect();
+    let old = async_std::fs::read_to_string(conf).await?;
+    let mut settings = ServerSettings::load(conf).await?;
+    settings.set_ssl(SERVER_CERT_FILE, SERVER_KEY_FILE);
+    ops.extend(edit_op(conf, &old, &settings.to_toml()));
+    Ok(ops)
+}
+/// the .orig copy made before the deployer first touches path
+fn backup_orig_op(path: &Path) -> Option<Operation> {
+    let orig = orig_path(path);
+    (path.exists() && !orig.exists()).then(|| Operation::CopyFile {
+        from: path.into(),
+        to: orig,
+    })
+}
+fn edit_op(path: &Path, old: &str, new: &str) -> Option<Operation> {
+    let diff = unified(&path.display().to_string(), old, new);
+    (!diff.is_empty()).then(|| Operation::EditFile {
+        path: path.into(),
+        diff,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_ssl_without_writing() {
+        let dir = std::env::temp_dir().join(format!("deploy-core-plan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let conf = dir.join("Settings.toml");
+        let template = "[encryption]\nssl_enable=false\ncert_file=\"\"\nkey_file=\"\"\n";
+        std::fs::write(&conf, template).unwrap();
+
+        let mut ops = servcert_ops(&conf);
+        ops.extend(async_std::task::block_on(ssl_ops(&conf)).unwrap());
+        let mut plan = Plan::new("192.168.1.2");
+        plan.push(StepKind::CertsIssued, ops);
+        plan.push(StepKind::CaImported, ca_ops(&dir));
+        assert!(plan.steps[0].operations.contains(&Operation::CopyFile {
+            from: conf.clone(),
+            to: dir.join("Settings.toml.orig"),
+        }));
+        let text = plan.to_string();
+        assert!(text.contains("\n-ssl_enable=false\n") && text.contains("\n+ssl_enable=true\n"));
+        assert!(text.contains("+cert_file=\"server.pem\""));
+        assert!(text.contains("6. write"));
+        assert_eq!(std::fs::read_to_string(&conf).unwrap(), template);
+        assert!(!dir.join(SERVER_CERT_FILE).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
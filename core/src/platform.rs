@@ -0,0 +1,120 @@
+use crate::error::ApplicationError;
+use crate::step_log::{self, Sink, Source};
+use std::fmt::{self, Debug};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+#[cfg(not(windows))]
+mod linux;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(not(windows))]
+pub use linux::Linux;
+#[cfg(windows)]
+pub use windows::Windows;
+
+/// everything the deployer needs to know about the OS it runs on
+///
+/// user dirs, Anki install and shortcut creation
+pub trait Platform: Debug + Sync {
+    /// home dir of current user
+    ///
+    /// C:\Users\Admin or /home/admin
+    fn user_profile(&self) -> Result<PathBuf, ApplicationError>;
+    fn desktop(&self) -> Result<PathBuf, ApplicationError>;
+    /// Anki's base folder which holds profiles and addons21
+    ///
+    /// C:\Users\Admin\AppData\Roaming\Anki2 or ~/.local/share/Anki2
+    fn anki_profile_dir(&self) -> Result<PathBuf, ApplicationError>;
+    /// installed PC Anki version,e.g. 2.1.36
+    fn anki_version(&self) -> Result<String, ApplicationError>;
+    /// dir where launcher entries live, start menu on Windows
+    fn start_menu_dir(&self) -> Result<PathBuf, ApplicationError>;
+    /// file name of ankisyncd executable in deployer's pre dir
+    fn ankisyncd_exe(&self) -> &'static str;
+    /// create a shortcut to target in dir named anki_server
+    ///
+    /// return path of the created shortcut
+    fn create_shortcut(&self, target: &Path, dir: &Path) -> Result<PathBuf, ApplicationError>;
+    /// add PEM encoded root CA to the system trust store
+    fn trust_ca(&self, ca_cert: &Path) -> Result<(), ApplicationError>;
+    /// remove the root CA added by trust_ca
+    fn untrust_ca(&self) -> Result<(), ApplicationError>;
+    /// persist an environment variable for the current user
+    fn set_user_env(&self, name: &str, value: &str) -> Result<(), ApplicationError>;
+    /// undo set_user_env
+    fn remove_user_env(&self, name: &str) -> Result<(), ApplicationError>;
+    /// process listening on tcp port,None if there is none or it can't be seen
+    fn port_owner(&self, port: u16) -> Option<PortOwner>;
+    /// user's UI locale,e.g. zh_CN.UTF-8 or zh-CN,None if it can't be read
+    fn locale(&self) -> Option<String>;
+
+    fn addon_dir(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.anki_profile_dir()?.join("addons21"))
+    }
+}
+
+/// process holding a port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub name: String,
+}
+impl fmt::Display for PortOwner {
+    /// ankisyncd (pid 1234)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+#[cfg(windows)]
+static CURRENT: Windows = Windows;
+#[cfg(not(windows))]
+static CURRENT: Linux = Linux;
+
+/// run cmd and turn a non-zero exit status into an error with its stderr
+///
+/// inside a step its stdout and stderr lines and exit status go to the step's log
+pub(crate) fn run(cmd: &mut Command) -> Result<(), ApplicationError> {
+    let sink = step_log::sink();
+    if let Some(sink) = &sink {
+        sink.log(Source::Command, format!("{:?}", cmd));
+    }
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    // read both at once,a child blocks once either pipe is full
+    let err_sink = sink.clone();
+    let err_reader = std::thread::spawn(move || read_lines(stderr, Source::Stderr, &err_sink));
+    read_lines(stdout, Source::Stdout, &sink);
+    let err = err_reader.join().unwrap_or_default();
+    let status = child.wait()?;
+    if let Some(sink) = &sink {
+        sink.log(Source::Status, status.to_string());
+    }
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ApplicationError::CommandFailed(
+            format!("{:?}", cmd),
+            err.join("\n").trim().to_owned(),
+        ))
+    }
+}
+/// lines of a child's pipe,each logged to sink as it arrives
+fn read_lines(pipe: impl Read, source: Source, sink: &Option<Sink>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+        if let Some(sink) = sink {
+            sink.log(source, line.clone());
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// platform the deployer is compiled for
+pub fn current() -> &'static dyn Platform {
+    &CURRENT
+}
@@ -0,0 +1,231 @@
+use super::{run, Platform, PortOwner};
+use crate::error::ApplicationError;
+use crate::safe_write::write_atomic;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Linux desktops following the XDG base directory spec
+#[derive(Debug, Clone, Copy)]
+pub struct Linux;
+
+impl Linux {
+    /// $XDG_DATA_HOME or ~/.local/share
+    fn data_home(&self) -> Result<PathBuf, ApplicationError> {
+        match env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => Ok(dir.into()),
+            _ => Ok(self.user_profile()?.join(".local").join("share")),
+        }
+    }
+    fn environment_d(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.user_profile()?.join(".config").join("environment.d"))
+    }
+    /// look up XDG_DESKTOP_DIR in ~/.config/user-dirs.dirs
+    ///
+    /// line looks like XDG_DESKTOP_DIR="$HOME/Desktop"
+    fn xdg_desktop_dir(&self, home: &Path) -> Option<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => home.join(".config"),
+        };
+        let contents = fs::read_to_string(config_home.join("user-dirs.dirs")).ok()?;
+        let value = contents
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix("XDG_DESKTOP_DIR="))?
+            .trim_matches('"');
+        match value.strip_prefix("$HOME") {
+            Some(rest) => Some(home.join(rest.trim_start_matches('/'))),
+            None => Some(value.into()),
+        }
+    }
+}
+
+/// Debian family picks up CAs from here via update-ca-certificates
+static DEBIAN_ANCHORS: &str = "/usr/local/share/ca-certificates";
+/// Fedora family via update-ca-trust
+static FEDORA_ANCHORS: &str = "/etc/pki/ca-trust/source/anchors";
+static ANCHOR_FILE: &str = "anki_server_rootCA.crt";
+
+impl Platform for Linux {
+    fn user_profile(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(env::var("HOME")?.into())
+    }
+    fn desktop(&self) -> Result<PathBuf, ApplicationError> {
+        let home = self.user_profile()?;
+        Ok(self
+            .xdg_desktop_dir(&home)
+            .unwrap_or_else(|| home.join("Desktop")))
+    }
+    fn anki_profile_dir(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.data_home()?.join("Anki2"))
+    }
+    /// ask the anki launcher on PATH,it prints Anki 2.1.36
+    fn anki_version(&self) -> Result<String, ApplicationError> {
+        let out = Command::new("anki").arg("--version").output()?;
+        version_line(&String::from_utf8(out.stdout)?)
+    }
+    /// ~/.local/share/applications
+    fn start_menu_dir(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.data_home()?.join("applications"))
+    }
+    fn ankisyncd_exe(&self) -> &'static str {
+        "ankisyncd"
+    }
+    /// write a .desktop entry which launches target in a terminal
+    fn create_shortcut(&self, target: &Path, dir: &Path) -> Result<PathBuf, ApplicationError> {
+        if !dir.exists() {
+            fs::create_dir_all(dir)?;
+        }
+        let entry = dir.join("anki_server.desktop");
+        let workdir = target.parent().unwrap_or_else(|| Path::new("."));
+        let contents = format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name=anki_server\n\
+             Exec=\"{}\"\n\
+             Path={}\n\
+             Terminal=true\n\
+             Categories=Network;\n",
+            target.display(),
+            workdir.display()
+        );
+        fs::write(&entry, contents)?;
+        // desktop environments only launch entries marked executable
+        fs::set_permissions(&entry, fs::Permissions::from_mode(0o755))?;
+        Ok(entry)
+    }
+    /// copy into the distro's anchors dir and refresh,needs root
+    fn trust_ca(&self, ca_cert: &Path) -> Result<(), ApplicationError> {
+        let (dir, refresh) = if Path::new(FEDORA_ANCHORS).exists() {
+            (FEDORA_ANCHORS, "update-ca-trust")
+        } else {
+            (DEBIAN_ANCHORS, "update-ca-certificates")
+        };
+        fs::create_dir_all(dir)?;
+        // update-ca-certificates only reads .crt files
+        fs::copy(ca_cert, Path::new(dir).join(ANCHOR_FILE))?;
+        run(&mut Command::new(refresh))
+    }
+    /// delete from whichever anchors dir holds it and refresh,needs root
+    fn untrust_ca(&self) -> Result<(), ApplicationError> {
+        // --fresh drops the stale link update-ca-certificates left in /etc/ssl/certs
+        let stores: [(&str, &str, &[&str]); 2] = [
+            (FEDORA_ANCHORS, "update-ca-trust", &[]),
+            (DEBIAN_ANCHORS, "update-ca-certificates", &["--fresh"]),
+        ];
+        for (dir, refresh, args) in stores {
+            let anchor = Path::new(dir).join(ANCHOR_FILE);
+            if anchor.exists() {
+                fs::remove_file(anchor)?;
+                run(Command::new(refresh).args(args))?;
+            }
+        }
+        Ok(())
+    }
+    /// ~/.config/environment.d is read by the systemd user session on login
+    fn set_user_env(&self, name: &str, value: &str) -> Result<(), ApplicationError> {
+        let dir = self.environment_d()?;
+        fs::create_dir_all(&dir)?;
+        write_atomic(
+            &dir.join(format!("{}.conf", name)),
+            format!("{}={}\n", name, value).as_bytes(),
+        )
+    }
+    fn remove_user_env(&self, name: &str) -> Result<(), ApplicationError> {
+        let conf = self.environment_d()?.join(format!("{}.conf", name));
+        if conf.exists() {
+            fs::remove_file(conf)?;
+        }
+        Ok(())
+    }
+    /// find the listening socket's inode in /proc/net,then the process with it open
+    ///
+    /// processes of other users can't be looked into without root
+    fn port_owner(&self, port: u16) -> Option<PortOwner> {
+        let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|table| fs::read_to_string(table).ok())
+            .flat_map(|table| listening_inodes(&table, port))
+            .collect();
+        let sockets: Vec<String> = inodes.iter().map(|i| format!("socket:[{}]", i)).collect();
+        fs::read_dir("/proc").ok()?.flatten().find_map(|proc| {
+            let pid: u32 = proc.file_name().to_str()?.parse().ok()?;
+            let holds = fs::read_dir(proc.path().join("fd"))
+                .ok()?
+                .flatten()
+                .filter_map(|fd| fs::read_link(fd.path()).ok())
+                .any(|link| sockets.iter().any(|s| link.as_os_str() == s.as_str()));
+            if !holds {
+                return None;
+            }
+            let name = fs::read_to_string(proc.path().join("comm")).ok()?;
+            Some(PortOwner {
+                pid,
+                name: name.trim().to_owned(),
+            })
+        })
+    }
+    /// the first of LC_ALL,LC_MESSAGES and LANG that is set,C and C.UTF-8 mean none
+    fn locale(&self) -> Option<String> {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|v| env::var(v).ok())
+            .find(|l| !l.is_empty())?;
+        Some(locale).filter(|l| l != "C" && !l.starts_with("C.") && l != "POSIX")
+    }
+}
+
+/// inodes of sockets listening on port in a /proc/net/tcp table
+///
+/// `sl local_address rem_address st ... inode`,address as hex ip:port,0A is LISTEN
+fn listening_inodes(table: &str, port: u16) -> Vec<String> {
+    let port = format!("{:04X}", port);
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            if local_port != port || *fields.get(3)? != "0A" {
+                return None;
+            }
+            fields.get(9).map(|i| i.to_string())
+        })
+        .collect()
+}
+/// anki --version's output as it is,e.g. Anki 2.1.66 (70506aeb)
+///
+/// AnkiVersion picks the version out,the last word may be a commit hash
+fn version_line(stdout: &str) -> Result<String, ApplicationError> {
+    match stdout.trim() {
+        "" => Err(ApplicationError::AnkiNotFound),
+        line => Ok(line.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listening_inode_of_port() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:6B25 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:6B25 0100007F:9C40 01 00000000:00000000 00:00000000 00000000  1000        0 41299 1 0000000000000000 20 4 30 10 -1
+   2: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1805 1 0000000000000000 100 0 0 10 0";
+        // 0x6B25 is 27429,the established connection on it doesn't count
+        assert_eq!(listening_inodes(table, 27429), vec!["41234"]);
+        assert!(listening_inodes(table, 27701).is_empty());
+    }
+
+    #[test]
+    fn version_despite_commit_hash() {
+        let line = version_line("Anki 2.1.66 (70506aeb)\n").unwrap();
+        let version: crate::anki::AnkiVersion = line.parse().unwrap();
+        assert_eq!(version, crate::anki::AnkiVersion::new(2, 1, 66));
+        assert!(version_line("\n").is_err());
+    }
+}
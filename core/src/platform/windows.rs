@@ -0,0 +1,126 @@
+use super::{run, Platform, PortOwner};
+use crate::ca::CA_COMMON_NAME;
+use crate::error::ApplicationError;
+use mslnk::ShellLink;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_SET_VALUE};
+use winreg::RegKey;
+
+static ANKI_UNINSTALL_KEY: &str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall\Anki";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Windows;
+
+impl Platform for Windows {
+    fn user_profile(&self) -> Result<PathBuf, ApplicationError> {
+        // userprofile:C:\\Users\\Admin
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let cur_ver = hkcu.open_subkey("Volatile Environment")?;
+        let userprofile: String = cur_ver.get_value("USERPROFILE")?;
+        Ok(userprofile.into())
+    }
+    fn desktop(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.user_profile()?.join("Desktop"))
+    }
+    fn anki_profile_dir(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self.user_profile()?.join(r"AppData\Roaming\Anki2"))
+    }
+    /// read anki ver from Windows register edit
+    ///
+    /// per-user installs first,then 64-bit and 32-bit machine-wide ones
+    fn anki_version(&self) -> Result<String, ApplicationError> {
+        let keys = [
+            (HKEY_CURRENT_USER, ANKI_UNINSTALL_KEY),
+            (HKEY_LOCAL_MACHINE, ANKI_UNINSTALL_KEY),
+            (
+                HKEY_LOCAL_MACHINE,
+                r"SOFTWARE\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall\Anki",
+            ),
+        ];
+        let mut last_err = None;
+        for (hive, path) in keys {
+            let found = RegKey::predef(hive)
+                .open_subkey(path)
+                .and_then(|key| key.get_value::<String, _>("DisplayVersion"));
+            match found {
+                Ok(ankiver) => return Ok(ankiver),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.expect("at least one key tried").into())
+    }
+    /// C:\Users\Admin\AppData\Roaming\Microsoft\Windows\Start Menu\Programs
+    fn start_menu_dir(&self) -> Result<PathBuf, ApplicationError> {
+        Ok(self
+            .user_profile()?
+            .join(r"AppData\Roaming\Microsoft\Windows\Start Menu\Programs"))
+    }
+    fn ankisyncd_exe(&self) -> &'static str {
+        "ankisyncd.exe"
+    }
+    fn create_shortcut(&self, target: &Path, dir: &Path) -> Result<PathBuf, ApplicationError> {
+        let lnk = dir.join("anki_server.lnk");
+        let sl = ShellLink::new(target)?;
+        sl.create_lnk(&lnk)?;
+        Ok(lnk)
+    }
+    /// certutil into the current user's Root store,Windows asks the user to confirm
+    fn trust_ca(&self, ca_cert: &Path) -> Result<(), ApplicationError> {
+        run(Command::new("certutil")
+            .args(&["-user", "-addstore", "-f", "Root"])
+            .arg(ca_cert))
+    }
+    /// delete by common name,the cert file may be gone already
+    fn untrust_ca(&self) -> Result<(), ApplicationError> {
+        run(Command::new("certutil")
+            .args(&["-user", "-delstore", "Root"])
+            .arg(CA_COMMON_NAME))
+    }
+    fn set_user_env(&self, name: &str, value: &str) -> Result<(), ApplicationError> {
+        run(Command::new("setx").args(&[name, value]))
+    }
+    /// setx can't delete,drop the value from HKCU\Environment
+    fn remove_user_env(&self, name: &str) -> Result<(), ApplicationError> {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let env = hkcu.open_subkey_with_flags("Environment", KEY_SET_VALUE)?;
+        match env.delete_value(name) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+    /// pid of the LISTENING line from netstat,its image name from tasklist
+    fn port_owner(&self, port: u16) -> Option<PortOwner> {
+        let out = Command::new("netstat")
+            .args(&["-ano", "-p", "TCP"])
+            .output()
+            .ok()?;
+        let suffix = format!(":{}", port);
+        // TCP    0.0.0.0:27701    0.0.0.0:0    LISTENING    1234
+        let pid: u32 = String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .find(|f| f.len() == 5 && f[1].ends_with(&suffix) && f[3] == "LISTENING")?[4]
+            .parse()
+            .ok()?;
+        let filter = format!("PID eq {}", pid);
+        let out = Command::new("tasklist")
+            .args(&["/FI", &filter, "/FO", "CSV", "/NH"])
+            .output()
+            .ok()?;
+        // "ankisyncd.exe","1234","Console","1","12,345 K"
+        let name = String::from_utf8_lossy(&out.stdout)
+            .split(',')
+            .next()
+            .map(|n| n.trim().trim_matches('"').to_owned())
+            .filter(|n| !n.is_empty() && !n.starts_with("INFO:"))
+            .unwrap_or_else(|| "?".into());
+        Some(PortOwner { pid, name })
+    }
+    /// LocaleName of the user's regional settings,e.g. zh-CN
+    fn locale(&self) -> Option<String> {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let intl = hkcu.open_subkey(r"Control Panel\International").ok()?;
+        intl.get_value("LocaleName").ok()
+    }
+}
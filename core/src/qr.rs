@@ -0,0 +1,48 @@
+use crate::addon_config::SyncAddr;
+use crate::error::ApplicationError;
+use crate::i18n::tr;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+
+/// urls AnkiDroid asks for,labelled as on its settings screen
+pub fn phone_urls(addr: &SyncAddr) -> [(&'static str, String); 2] {
+    [
+        (tr("qr.sync_url"), addr.base_url()),
+        (tr("qr.media_url"), format!("{}/msync", addr.base_url())),
+    ]
+}
+
+/// QR code of data drawn with half blocks,two modules per character row
+///
+/// colors are inverted so it scans on dark terminals too
+pub fn terminal_qr(data: &str) -> Result<String, ApplicationError> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| ApplicationError::Qr(e.to_string()))?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addon_config::Scheme;
+
+    #[test]
+    fn urls_and_blocks() {
+        let addr = SyncAddr::new(Scheme::Https, "192.168.1.2", 27701);
+        let urls = phone_urls(&addr);
+        assert_eq!(urls[0].1, "https://192.168.1.2:27701");
+        assert_eq!(urls[1].1, "https://192.168.1.2:27701/msync");
+
+        let qr = terminal_qr(&urls[0].1).unwrap();
+        let rows: Vec<&str> = qr.lines().collect();
+        // square code,half as many rows as columns
+        let width = rows[0].chars().count();
+        assert!(width > 20);
+        assert_eq!(rows.len(), width.div_ceil(2));
+        assert!(rows.iter().all(|r| r.chars().count() == width));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::addon_config::AddonConfig;
+use crate::anki::Profiles;
+use crate::ca::{SERVER_CERT_FILE, SERVER_KEY_FILE};
+use crate::error::ApplicationError;
+use crate::logic::{manifest_path, orig_path, root_dir, CONF_PATH, NOVERIFYSSL_ENV};
+use crate::manifest::{Manifest, Outcome, StepKind, StepRecord};
+use crate::platform::Platform;
+use crate::safe_write::safe_write;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// what uninstall reverted and what it had to leave behind
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RollbackReport {
+    pub reverted: Vec<String>,
+    /// action and the error which stopped it
+    pub failed: Vec<(String, String)>,
+}
+impl RollbackReport {
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+    fn push(&mut self, action: String, result: Result<(), ApplicationError>) {
+        match result {
+            Ok(()) => self.reverted.push(action),
+            Err(e) => self.failed.push((action, e.to_string())),
+        }
+    }
+}
+
+/// undo every step the manifest records as done,newest first
+///
+/// keeps going past failures,a clean run forgets the deployment
+pub async fn uninstall(platform: &dyn Platform) -> Result<RollbackReport, ApplicationError> {
+    let path = manifest_path();
+    let mut manifest = Manifest::load(&path).await?;
+    let mut report = RollbackReport::default();
+
+    for step in [StepKind::StartMenuAdded, StepKind::ShortcutSent] {
+        for shortcut in recorded_paths(&manifest, step) {
+            let result = remove_file(&shortcut).await;
+            report.push(format!("remove shortcut {}", shortcut.display()), result);
+        }
+    }
+    if ever_done(&manifest, StepKind::CertsIssued) {
+        let result = restore_settings().await;
+        report.push(format!("restore {}", CONF_PATH), result);
+        let result = platform.remove_user_env(NOVERIFYSSL_ENV);
+        report.push(format!("remove {}", NOVERIFYSSL_ENV), result);
+    }
+    if ever_done(&manifest, StepKind::CaImported) {
+        report.push("untrust root CA".into(), platform.untrust_ca());
+        let result = match platform.desktop() {
+            Ok(desktop) => remove_file(&desktop.join("rootCA.crt")).await,
+            Err(e) => Err(e),
+        };
+        report.push("remove rootCA.crt from desktop".into(), result);
+    }
+    if let Some(first) = first_pcip(&manifest, "builtin") {
+        let result = match platform.anki_profile_dir() {
+            Ok(dir) => restore_sync_urls(&Profiles::path_in(&dir), first),
+            Err(e) => Err(e),
+        };
+        report.push("restore PC Anki sync server".into(), result);
+    }
+    if first_pcip(&manifest, "addon").is_some() {
+        let result = match platform.addon_dir() {
+            Ok(dir) => remove_addon(&dir.join("ankisyncd")).await,
+            Err(e) => Err(e),
+        };
+        report.push("remove ankisyncd addon".into(), result);
+    }
+
+    if report.is_clean() {
+        manifest.forget_deployment();
+        manifest.save(&path).await?;
+    }
+    Ok(report)
+}
+
+fn ever_done(manifest: &Manifest, step: StepKind) -> bool {
+    manifest
+        .steps
+        .iter()
+        .any(|r| r.step == step && r.outcome == Outcome::Ok)
+}
+/// first successful set ip in mode,it saw PC Anki as the user left it
+///
+/// manifests from before built-in sync support have no mode,they copied the addon
+fn first_pcip<'a>(manifest: &'a Manifest, mode: &str) -> Option<&'a StepRecord> {
+    manifest.steps.iter().find(|r| {
+        r.step == StepKind::PcipModified
+            && r.outcome == Outcome::Ok
+            && r.inputs.get("mode").map_or("addon", String::as_str) == mode
+    })
+}
+/// every distinct path a successful run of step recorded
+fn recorded_paths(manifest: &Manifest, step: StepKind) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for r in manifest.steps.iter() {
+        if r.step != step || r.outcome != Outcome::Ok {
+            continue;
+        }
+        if let Some(p) = r.inputs.get("path").filter(|p| !p.is_empty()) {
+            let p = PathBuf::from(p);
+            if !paths.contains(&p) {
+                paths.push(p);
+            }
+        }
+    }
+    paths
+}
+/// already gone counts as removed
+async fn remove_file(path: &Path) -> Result<(), ApplicationError> {
+    if path.exists() {
+        async_std::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+/// write orig over conf and drop it,what the deployer wrote stays as a backup
+async fn put_back(orig: &Path, conf: &Path) -> Result<(), ApplicationError> {
+    safe_write(conf, &async_std::fs::read(orig).await?)?;
+    remove_file(orig).await
+}
+/// put Settings.toml back as shipped and drop the issued server cert
+async fn restore_settings() -> Result<(), ApplicationError> {
+    let conf = root_dir().join(CONF_PATH);
+    let orig = orig_path(&conf);
+    if orig.exists() {
+        put_back(&orig, &conf).await?;
+    }
+    remove_file(&root_dir().join(SERVER_CERT_FILE)).await?;
+    remove_file(&root_dir().join(SERVER_KEY_FILE)).await
+}
+/// an addon the user had before only gets its config back
+async fn remove_addon(dir: &Path) -> Result<(), ApplicationError> {
+    let conf = AddonConfig::path_in(dir);
+    let orig = orig_path(&conf);
+    if orig.exists() {
+        put_back(&orig, &conf).await?;
+    } else if dir.exists() {
+        async_std::fs::remove_dir_all(dir).await?;
+    }
+    Ok(())
+}
+/// give each profile set ip saw its sync server back,AnkiWeb where it had none
+fn restore_sync_urls(db: &Path, record: &StepRecord) -> Result<(), ApplicationError> {
+    let profiles = Profiles::open(db)?;
+    let names = profiles.names()?;
+    for (key, url) in record.inputs.iter() {
+        // profiles deleted since have nothing to restore
+        if let Some(name) = key
+            .strip_prefix("previous:")
+            .filter(|n| names.iter().any(|x| x == n))
+        {
+            let url = Some(url.as_str()).filter(|u| !u.is_empty());
+            profiles.set_custom_sync_url(name, url)?;
+        }
+    }
+    Ok(())
+}
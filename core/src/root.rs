@@ -0,0 +1,85 @@
+use crate::error::ApplicationError;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// dir next to the deployer holding ankisyncd,its config and the deployer's state
+pub static PRE_DIR: &str = "pre";
+
+/// the deployer's dir,an absolute path resolved once at startup
+///
+/// every path the deployer reads or writes is built from it,the process's
+/// working directory is never looked at again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentRoot {
+    dir: PathBuf,
+}
+impl DeploymentRoot {
+    /// a relative dir is taken against the working directory now
+    pub fn new(dir: impl AsRef<Path>) -> Result<DeploymentRoot, ApplicationError> {
+        Ok(DeploymentRoot {
+            dir: std::path::absolute(dir)?,
+        })
+    }
+    /// dir of the running executable,or its parent for one inside pre like updateaddr
+    ///
+    /// the working directory if neither has a pre dir,e.g. under cargo run
+    pub fn from_exe() -> Result<DeploymentRoot, ApplicationError> {
+        let exe = std::env::current_exe()?;
+        match base_of(&exe) {
+            Some(dir) => DeploymentRoot::new(dir),
+            None => DeploymentRoot::new(std::env::current_dir()?),
+        }
+    }
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// dir/pre
+    pub fn pre(&self) -> PathBuf {
+        self.dir.join(PRE_DIR)
+    }
+}
+/// the first of exe's dir and its parent which has a pre dir
+fn base_of(exe: &Path) -> Option<&Path> {
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join(PRE_DIR).is_dir())
+}
+
+static CURRENT: OnceLock<DeploymentRoot> = OnceLock::new();
+
+/// make root the one every operation uses,only the first call has an effect
+pub fn init(root: DeploymentRoot) -> &'static DeploymentRoot {
+    CURRENT.get_or_init(|| root)
+}
+/// root given to init,else the executable's,else the working directory
+///
+/// binaries call init first and report a root that can't be resolved,
+/// panics only if neither the executable nor the working directory is known
+pub fn current() -> &'static DeploymentRoot {
+    CURRENT.get_or_init(|| {
+        DeploymentRoot::from_exe()
+            .or_else(|_| DeploymentRoot::new(std::env::current_dir()?))
+            .expect("can't resolve the deployment root,pass --root")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_pre_next_to_or_above_exe() {
+        let dir = std::env::temp_dir().join(format!("deploy-core-root-{}", std::process::id()));
+        let pre = dir.join(PRE_DIR);
+        std::fs::create_dir_all(&pre).unwrap();
+        assert_eq!(base_of(&dir.join("deployer")), Some(dir.as_path()));
+        assert_eq!(base_of(&pre.join("updateaddr")), Some(dir.as_path()));
+        assert_eq!(base_of(&pre.join("ca").join("x")), None);
+
+        let root = DeploymentRoot::new("relative").unwrap();
+        assert!(root.dir().is_absolute() && root.dir().ends_with("relative"));
+        assert_eq!(root.pre(), root.dir().join(PRE_DIR));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
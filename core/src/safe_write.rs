@@ -0,0 +1,195 @@
+use crate::backup::{parse_stamp, stamp};
+use crate::error::ApplicationError;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// earlier versions kept of each config file,older ones are deleted
+pub static KEEP_BACKUPS: usize = 5;
+static BACKUP_SUFFIX: &str = ".bak";
+
+/// an earlier version of a config file,Settings.toml.20261018T153000Z.bak next to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileBackup {
+    /// the file it was taken of
+    pub original: PathBuf,
+    pub path: PathBuf,
+    pub created: OffsetDateTime,
+    pub size: u64,
+}
+
+/// replace path with contents,keeping what was there as a timestamped backup
+///
+/// nothing is backed up if path is new or already has contents
+pub fn safe_write(path: &Path, contents: &[u8]) -> Result<(), ApplicationError> {
+    match fs::read(path) {
+        Ok(old) if old != contents => keep_backup(path, &old)?,
+        Ok(_) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    write_atomic(path, contents)
+}
+/// write a temp file in path's dir,fsync it and rename it over path
+///
+/// a crash leaves either the old or the new file,never a truncated one
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), ApplicationError> {
+    replace(path, contents, false)
+}
+/// write_atomic for private keys,only the current user may read the file
+///
+/// the temp file is restricted before anything is written to it
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), ApplicationError> {
+    replace(path, contents, true)
+}
+/// take access to path away from everyone but the current user
+///
+/// mode 0600 on unix,an ACL granting only the current user on Windows
+pub fn restrict_to_owner(path: &Path) -> Result<(), ApplicationError> {
+    restrict(path).map_err(Into::into)
+}
+#[cfg(unix)]
+fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+#[cfg(windows)]
+fn restrict(path: &Path) -> io::Result<()> {
+    let user = std::env::var("USERNAME").map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let status = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{}:F", user))
+        .output()?
+        .status;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("icacls {:?} {}", path, status),
+        ))
+    }
+}
+/// temp file next to path,renamed over it once contents are on disk
+fn replace(path: &Path, contents: &[u8], private: bool) -> Result<(), ApplicationError> {
+    let tmp = with_suffix(path, ".tmp");
+    let written = create_tmp(&tmp, private).and_then(|mut f| {
+        f.write_all(contents)?;
+        f.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    // the rename itself only survives a power cut once the dir is synced
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+/// backups of path,newest first
+pub fn backups(path: &Path) -> Result<Vec<FileBackup>, ApplicationError> {
+    let dir = match path.parent() {
+        Some(d) if d.as_os_str().is_empty() => Path::new("."),
+        Some(d) => d,
+        None => return Ok(Vec::new()),
+    };
+    let prefix = with_suffix(Path::new(path.file_name().unwrap_or_default()), ".");
+    let prefix = prefix.to_string_lossy();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let created = name
+            .to_str()
+            .and_then(|n| n.strip_prefix(prefix.as_ref()))
+            .and_then(|n| n.strip_suffix(BACKUP_SUFFIX))
+            .and_then(parse_stamp);
+        if let Some(created) = created {
+            found.push(FileBackup {
+                original: path.into(),
+                path: path.with_file_name(name),
+                created,
+                size: entry.metadata()?.len(),
+            });
+        }
+    }
+    found.sort_by_key(|b| std::cmp::Reverse(b.created));
+    Ok(found)
+}
+/// put backup's contents back,what it replaces is backed up in turn
+pub fn restore(backup: &FileBackup) -> Result<(), ApplicationError> {
+    safe_write(&backup.original, &fs::read(&backup.path)?)
+}
+/// save old as the newest backup of path and drop those beyond KEEP_BACKUPS
+///
+/// a second write within the same second keeps the first backup,the older state
+fn keep_backup(path: &Path, old: &[u8]) -> Result<(), ApplicationError> {
+    let name = format!(".{}{}", stamp(OffsetDateTime::now_utc()), BACKUP_SUFFIX);
+    let backup = with_suffix(path, &name);
+    if !backup.exists() {
+        write_atomic(&backup, old)?;
+    }
+    for expired in backups(path)?.iter().skip(KEEP_BACKUPS) {
+        fs::remove_file(&expired.path)?;
+    }
+    Ok(())
+}
+fn create_tmp(tmp: &Path, private: bool) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(tmp)?;
+    // mode only applies to new files,a leftover temp file keeps its own
+    if private {
+        restrict(tmp)?;
+    }
+    Ok(file)
+}
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_up_rotates_and_restores() {
+        let dir = std::env::temp_dir().join(format!("deploy-core-safe-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let conf = dir.join("Settings.toml");
+        for i in 0..KEEP_BACKUPS + 2 {
+            let old = dir.join(format!("Settings.toml.2020010{}T000000Z.bak", i + 1));
+            fs::write(old, format!("v{}", i)).unwrap();
+        }
+
+        safe_write(&conf, b"first").unwrap();
+        assert_eq!(backups(&conf).unwrap().len(), KEEP_BACKUPS + 2);
+        safe_write(&conf, b"second").unwrap();
+        // same contents,nothing to back up
+        safe_write(&conf, b"second").unwrap();
+        let kept = backups(&conf).unwrap();
+        assert_eq!(kept.len(), KEEP_BACKUPS);
+        assert_eq!(fs::read(&kept[0].path).unwrap(), b"first");
+        assert_eq!(fs::read_to_string(&kept[1].path).unwrap(), "v6");
+        assert!(!dir.join("Settings.toml.tmp").exists());
+
+        restore(&kept[0]).unwrap();
+        assert_eq!(fs::read(&conf).unwrap(), b"first");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use crate::error::ApplicationError;
+use crate::safe_write::safe_write;
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Document, Item, Table};
+
+/// ankisyncd's Settings.toml
+///
+/// typed view over the parsed document,save writes the fields back
+/// and keeps comments, formatting and unknown keys as they were
+#[derive(Debug, Clone)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    pub root_dir: PathBuf,
+    /// sqlite db of sync accounts,relative to root_dir
+    pub auth_db_path: PathBuf,
+    pub ssl_enable: bool,
+    pub cert_file: Option<PathBuf>,
+    pub key_file: Option<PathBuf>,
+    path: PathBuf,
+    doc: Document,
+}
+/// (table,key) each field is stored under by default
+static HOST: (&str, &str) = ("address", "host");
+static PORT: (&str, &str) = ("address", "port");
+static ROOT_DIR: (&str, &str) = ("paths", "root_dir");
+static AUTH_DB_PATH: (&str, &str) = ("paths", "auth_db_path");
+static SSL_ENABLE: (&str, &str) = ("encryption", "ssl_enable");
+static CERT_FILE: (&str, &str) = ("encryption", "cert_file");
+static KEY_FILE: (&str, &str) = ("encryption", "key_file");
+
+impl ServerSettings {
+    pub async fn load(path: impl Into<PathBuf>) -> Result<ServerSettings, ApplicationError> {
+        let path = path.into();
+        let contents = async_std::fs::read_to_string(&path).await?;
+        Self::parse(path, &contents)
+    }
+    fn parse(path: PathBuf, contents: &str) -> Result<ServerSettings, ApplicationError> {
+        let doc = contents.parse::<Document>()?;
+        let string = |key| {
+            lookup(&doc, key)
+                .and_then(|i| i.as_str())
+                .map(ToOwned::to_owned)
+        };
+        // port is written as "27701" by ankisyncd's template,accept integers too
+        let port = match lookup(&doc, PORT) {
+            Some(i) if i.is_integer() => i.as_integer().map(|p| {
+                u16::try_from(p).map_err(|_| ApplicationError::InvalidPort(p.to_string()))
+            }),
+            Some(i) => i.as_str().map(|p| {
+                p.trim()
+                    .parse::<u16>()
+                    .map_err(|_| ApplicationError::InvalidPort(p.to_owned()))
+            }),
+            None => None,
+        }
+        .transpose()?;
+        let non_empty = |s: Option<String>| s.filter(|s| !s.is_empty()).map(PathBuf::from);
+
+        Ok(ServerSettings {
+            host: string(HOST).unwrap_or_else(|| "0.0.0.0".into()),
+            port: port.unwrap_or(27701),
+            root_dir: string(ROOT_DIR).unwrap_or_else(|| ".".into()).into(),
+            auth_db_path: non_empty(string(AUTH_DB_PATH)).unwrap_or_else(|| "auth.db".into()),
+            ssl_enable: lookup(&doc, SSL_ENABLE)
+                .and_then(|i| i.as_bool())
+                .unwrap_or(false),
+            cert_file: non_empty(string(CERT_FILE)),
+            key_file: non_empty(string(KEY_FILE)),
+            path,
+            doc,
+        })
+    }
+    /// dir of Settings.toml,relative paths in it are resolved against it
+    pub fn base_dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new("."))
+    }
+    pub fn resolve(&self, p: &Path) -> PathBuf {
+        self.base_dir().join(p)
+    }
+    /// auth db ankisyncd reads accounts from
+    pub fn auth_db(&self) -> PathBuf {
+        self.resolve(&self.root_dir.join(&self.auth_db_path))
+    }
+    /// enable ssl with cert and key file paths,relative to Settings.toml dir
+    pub fn set_ssl(&mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) {
+        self.ssl_enable = true;
+        self.cert_file = Some(cert.into());
+        self.key_file = Some(key.into());
+    }
+    /// when ssl is enabled, cert and key files must exist
+    pub fn validate(&self) -> Result<(), ApplicationError> {
+        if !self.ssl_enable {
+            return Ok(());
+        }
+        let cert = self
+            .cert_file
+            .as_ref()
+            .ok_or_else(|| ApplicationError::CertFileMissing(PathBuf::new()))?;
+        if !self.resolve(cert).exists() {
+            return Err(ApplicationError::CertFileMissing(cert.clone()));
+        }
+        let key = self
+            .key_file
+            .as_ref()
+            .ok_or_else(|| ApplicationError::KeyFileMissing(PathBuf::new()))?;
+        if !self.resolve(key).exists() {
+            return Err(ApplicationError::KeyFileMissing(key.clone()));
+        }
+        Ok(())
+    }
+    /// render fields into the document
+    pub fn to_toml(&self) -> String {
+        let mut doc = self.doc.clone();
+        store(&mut doc, HOST, value(self.host.as_str()));
+        // keep whichever type the port was written in
+        let port = match lookup(&doc, PORT) {
+            Some(i) if i.is_integer() => value(i64::from(self.port)),
+            _ => value(self.port.to_string()),
+        };
+        store(&mut doc, PORT, port);
+        store(&mut doc, ROOT_DIR, value(path_str(&self.root_dir)));
+        // templates leave it out,it's only added once it differs from the default
+        if lookup(&doc, AUTH_DB_PATH).is_some() || self.auth_db_path != Path::new("auth.db") {
+            store(&mut doc, AUTH_DB_PATH, value(path_str(&self.auth_db_path)));
+        }
+        store(&mut doc, SSL_ENABLE, value(self.ssl_enable));
+        let opt_path = |p: &Option<PathBuf>| value(p.as_deref().map(path_str).unwrap_or_default());
+        store(&mut doc, CERT_FILE, opt_path(&self.cert_file));
+        store(&mut doc, KEY_FILE, opt_path(&self.key_file));
+        doc.to_string()
+    }
+    /// validate and write back to Settings.toml,the old one is kept as a backup
+    pub async fn save(&self) -> Result<(), ApplicationError> {
+        self.validate()?;
+        safe_write(&self.path, self.to_toml().as_bytes())
+    }
+}
+/// find key in its table, old templates put everything at top level
+fn lookup<'a>(doc: &'a Document, (table, key): (&str, &str)) -> Option<&'a Item> {
+    doc.get(table)
+        .and_then(|t| t.get(key))
+        .or_else(|| doc.get(key))
+        .filter(|i| i.is_value())
+}
+/// overwrite key where it already lives, otherwise add it to its table
+fn store(doc: &mut Document, (table, key): (&str, &str), mut new: Item) {
+    let in_table = doc
+        .get(table)
+        .and_then(|t| t.get(key))
+        .is_some_and(|i| i.is_value());
+    let at_top = doc.get(key).is_some_and(|i| i.is_value());
+    if !in_table && !at_top && !doc.contains_key(table) {
+        doc.insert(table, Item::Table(Table::new()));
+    }
+    let slot = if !in_table && at_top {
+        &mut doc[key]
+    } else {
+        &mut doc[table][key]
+    };
+    // carry over comments and spacing around the old value
+    if let (Some(old), Some(new)) = (slot.as_value(), new.as_value_mut()) {
+        *new.decor_mut() = old.decor().clone();
+    }
+    *slot = new;
+}
+fn path_str(p: &Path) -> String {
+    p.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TEMPLATE: &str = r#"# ankisyncd settings
+[address]
+host="0.0.0.0"
+port = "27701" # sync port
+
+[paths]
+root_dir="."
+data_extra = "keep me"
+
+[encryption]
+ssl_enable=false
+cert_file=""
+key_file=""
+"#;
+
+    #[test]
+    fn parse_template() {
+        let s = ServerSettings::parse("Settings.toml".into(), TEMPLATE).unwrap();
+        assert_eq!(s.port, 27701);
+        assert_eq!(s.host, "0.0.0.0");
+        assert!(!s.ssl_enable);
+        assert_eq!(s.cert_file, None);
+        assert_eq!(s.auth_db(), Path::new("./auth.db"));
+    }
+
+    #[test]
+    fn roundtrip_keeps_comments_and_unknown_keys() {
+        let mut s = ServerSettings::parse("Settings.toml".into(), TEMPLATE).unwrap();
+        s.set_ssl("localhost+3.pem", "localhost+3-key.pem");
+        let out = s.to_toml();
+        assert!(out.contains("# ankisyncd settings"));
+        assert!(out.contains("port = \"27701\" # sync port"));
+        assert!(out.contains("data_extra = \"keep me\""));
+        assert!(out.contains("ssl_enable=true"));
+        assert!(out.contains("cert_file=\"localhost+3.pem\""));
+
+        let back = ServerSettings::parse("Settings.toml".into(), &out).unwrap();
+        assert!(back.ssl_enable);
+        assert_eq!(back.key_file, Some(PathBuf::from("localhost+3-key.pem")));
+    }
+
+    #[test]
+    fn port_out_of_range_is_an_error() {
+        let doc = TEMPLATE.replace("\"27701\"", "70000");
+        assert!(matches!(
+            ServerSettings::parse("Settings.toml".into(), &doc),
+            Err(ApplicationError::InvalidPort(p)) if p == "70000"
+        ));
+        let doc = TEMPLATE.replace("27701", "-1");
+        assert!(ServerSettings::parse("Settings.toml".into(), &doc).is_err());
+    }
+
+    #[test]
+    fn roundtrip_keeps_auth_db_path() {
+        let mut s = ServerSettings::parse("Settings.toml".into(), TEMPLATE).unwrap();
+        assert!(!s.to_toml().contains("auth_db_path"));
+        s.auth_db_path = "accounts/auth.db".into();
+        let back = ServerSettings::parse("Settings.toml".into(), &s.to_toml()).unwrap();
+        assert_eq!(back.auth_db_path, PathBuf::from("accounts/auth.db"));
+        assert_eq!(back.auth_db(), Path::new("./accounts/auth.db"));
+        assert_eq!(back.to_toml(), s.to_toml());
+    }
+
+    #[test]
+    fn missing_cert_is_an_error() {
+        let mut s = ServerSettings::parse("Settings.toml".into(), TEMPLATE).unwrap();
+        s.set_ssl("no-such-cert.pem", "no-such-key.pem");
+        assert!(matches!(
+            s.validate(),
+            Err(ApplicationError::CertFileMissing(_))
+        ));
+    }
+}
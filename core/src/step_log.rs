@@ -0,0 +1,130 @@
+use crate::error::ApplicationError;
+use crate::manifest::StepKind;
+use async_std::channel::Sender;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use time::format_description::BorrowedFormatItem;
+use time::OffsetDateTime;
+
+/// where a line of a step's log came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// an external command about to run
+    Command,
+    Stdout,
+    Stderr,
+    /// exit status of a command or outcome of the step
+    Status,
+}
+/// one line a step logged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub step: StepKind,
+    pub time: OffsetDateTime,
+    pub source: Source,
+    pub text: String,
+}
+impl fmt::Display for LogLine {
+    /// 15:30:00 CertsIssued $ certutil -addstore Root rootCA.crt
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.source {
+            Source::Command => '$',
+            Source::Stdout => '|',
+            Source::Stderr => '!',
+            Source::Status => '=',
+        };
+        let time = self.time.format(&time_format()).map_err(|_| fmt::Error)?;
+        write!(f, "{} {:?} {} {}", time, self.step, mark, self.text)
+    }
+}
+fn time_format() -> Vec<BorrowedFormatItem<'static>> {
+    time::format_description::parse_borrowed::<1>("[hour]:[minute]:[second]")
+        .expect("valid format description")
+}
+
+/// the running step and where its lines go
+#[derive(Debug, Clone)]
+pub(crate) struct Sink {
+    step: StepKind,
+    tx: Sender<LogLine>,
+}
+impl Sink {
+    pub(crate) fn log(&self, source: Source, text: impl Into<String>) {
+        // the receiver is gone once the page stops listening,the step still finishes
+        let _ = self.tx.try_send(LogLine {
+            step: self.step,
+            time: OffsetDateTime::now_utc(),
+            source,
+            text: text.into(),
+        });
+    }
+}
+
+async_std::task_local! {
+    static SINK: RefCell<Option<Sink>> = RefCell::new(None);
+}
+
+/// run step's future in a task of its own,every line it logs is sent to tx
+///
+/// tx is dropped once fut is done,the receiver sees the channel close
+pub async fn capture<T, F>(step: StepKind, tx: Sender<LogLine>, fut: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    async_std::task::spawn(async move {
+        SINK.with(|s| *s.borrow_mut() = Some(Sink { step, tx }));
+        let out = fut.await;
+        SINK.with(|s| s.borrow_mut().take());
+        out
+    })
+    .await
+}
+/// sink of the step running in this task,None outside capture
+///
+/// cloned for threads reading a child's output,they don't see task locals
+pub(crate) fn sink() -> Option<Sink> {
+    SINK.try_with(|s| s.borrow().clone()).ok().flatten()
+}
+/// log a line to the step running in this task,dropped outside capture
+pub fn log(source: Source, text: impl Into<String>) {
+    if let Some(sink) = sink() {
+        sink.log(source, text);
+    }
+}
+/// write lines to path,one per line as they're displayed
+pub async fn save(lines: &[LogLine], path: &Path) -> Result<(), ApplicationError> {
+    let text: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+    async_std::fs::write(path, text).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_lines_of_its_task_only() {
+        let (tx, rx) = async_std::channel::unbounded();
+        let got = async_std::task::block_on(async {
+            log(Source::Stdout, "not captured");
+            capture(StepKind::CaImported, tx, async {
+                log(Source::Command, "certutil -addstore Root rootCA.crt");
+                log(Source::Status, "exit status: 0");
+                7
+            })
+            .await
+        });
+        assert_eq!(got, 7);
+        let lines: Vec<LogLine> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].step, StepKind::CaImported);
+        assert!(lines[0]
+            .to_string()
+            .ends_with(" CaImported $ certutil -addstore Root rootCA.crt"));
+        assert_eq!(lines[1].source, Source::Status);
+        assert!(rx.is_closed());
+    }
+}
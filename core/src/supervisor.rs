@@ -0,0 +1,432 @@
+use crate::logic::{root_dir, scheduled_backup};
+use crate::platform;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// lines of ankisyncd output kept for the server page
+pub static LOG_CAPACITY: usize = 500;
+/// first restart delay,doubled after every crash
+static BACKOFF_START: Duration = Duration::from_secs(1);
+static BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// a run this long counts as healthy and resets the backoff
+static STABLE_AFTER: Duration = Duration::from_secs(30);
+static POLL: Duration = Duration::from_millis(200);
+/// how often the backup policy is checked while ankisyncd is wanted
+static BACKUP_CHECK: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerState {
+    Stopped,
+    Running {
+        pid: u32,
+    },
+    /// exited on its own,restarted after retry_in
+    Crashed {
+        code: Option<i32>,
+        restarts: u32,
+        retry_in: Duration,
+    },
+    /// couldn't be spawned at all,e.g. ankisyncd missing
+    Failed(String),
+}
+impl fmt::Display for ServerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerState::Stopped => write!(f, "stopped"),
+            ServerState::Running { pid } => write!(f, "running,pid {}", pid),
+            ServerState::Crashed {
+                code,
+                restarts,
+                retry_in,
+            } => {
+                match code {
+                    Some(code) => write!(f, "exited with code {}", code)?,
+                    None => write!(f, "killed by signal")?,
+                }
+                write!(f, ",restart {} in {}s", restarts, retry_in.as_secs())
+            }
+            ServerState::Failed(e) => write!(f, "failed to start: {}", e),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub stream: Stream,
+    pub text: String,
+}
+/// snapshot for the UI and CLI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub state: ServerState,
+    pub log: Vec<LogLine>,
+    /// lines captured since start,tells watchers how many of log are new
+    pub logged: u64,
+    /// bumped on every change,lets watchers skip identical snapshots
+    pub generation: u64,
+}
+
+struct Inner {
+    state: ServerState,
+    log: VecDeque<LogLine>,
+    logged: u64,
+    generation: u64,
+    want_running: bool,
+    /// restart asked for,the backoff after a crash is cut short
+    restart_now: bool,
+    child: Option<Child>,
+    worker: bool,
+    scheduler: bool,
+}
+/// keeps ankisyncd running in the pre dir,which holds its Settings.toml
+pub struct Supervisor {
+    exe: PathBuf,
+    workdir: PathBuf,
+    /// run scheduled backups while started
+    backups: bool,
+    inner: Arc<Mutex<Inner>>,
+    /// wakes the worker out of its backoff on stop or restart
+    wake: Arc<Condvar>,
+}
+
+/// the deployer's one supervisor,nothing is spawned before start
+pub fn supervisor() -> &'static Supervisor {
+    static SUPERVISOR: OnceLock<Supervisor> = OnceLock::new();
+    SUPERVISOR.get_or_init(|| {
+        let workdir = root_dir();
+        Supervisor::new(workdir.join(platform::current().ankisyncd_exe()), workdir).with_backups()
+    })
+}
+
+impl Supervisor {
+    pub fn new(exe: PathBuf, workdir: PathBuf) -> Supervisor {
+        Supervisor {
+            exe,
+            workdir,
+            backups: false,
+            inner: Arc::new(Mutex::new(Inner {
+                state: ServerState::Stopped,
+                log: VecDeque::with_capacity(LOG_CAPACITY),
+                logged: 0,
+                generation: 0,
+                want_running: false,
+                restart_now: false,
+                child: None,
+                worker: false,
+                scheduler: false,
+            })),
+            wake: Arc::new(Condvar::new()),
+        }
+    }
+    /// back up ankisyncd's data per the manifest's policy while started
+    pub fn with_backups(mut self) -> Supervisor {
+        self.backups = true;
+        self
+    }
+    pub fn status(&self) -> ServerStatus {
+        let inner = lock(&self.inner);
+        ServerStatus {
+            state: inner.state.clone(),
+            log: inner.log.iter().cloned().collect(),
+            logged: inner.logged,
+            generation: inner.generation,
+        }
+    }
+    /// next status differing from generation seen,the current one if None
+    pub async fn changed(&self, seen: Option<u64>) -> ServerStatus {
+        while seen == Some(lock(&self.inner).generation) {
+            async_std::task::sleep(POLL).await;
+        }
+        self.status()
+    }
+    /// spawn ankisyncd and keep it up until stop
+    pub fn start(&self) {
+        let mut inner = lock(&self.inner);
+        inner.want_running = true;
+        if !inner.worker {
+            inner.worker = true;
+            let (exe, workdir, shared, wake) = (
+                self.exe.clone(),
+                self.workdir.clone(),
+                self.inner.clone(),
+                self.wake.clone(),
+            );
+            thread::spawn(move || watch(exe, workdir, shared, wake));
+        }
+        if self.backups && !inner.scheduler {
+            inner.scheduler = true;
+            let shared = self.inner.clone();
+            thread::spawn(move || schedule_backups(shared));
+        }
+    }
+    pub fn stop(&self) {
+        let mut inner = lock(&self.inner);
+        inner.want_running = false;
+        if let Some(child) = inner.child.as_mut() {
+            let _ = child.kill();
+        } else if matches!(inner.state, ServerState::Crashed { .. }) {
+            // waiting out the backoff,nothing to kill
+            set_state(&mut inner, ServerState::Stopped);
+        }
+        self.wake.notify_all();
+    }
+    /// stop and wait until the process is gone
+    pub async fn stop_and_wait(&self) {
+        self.stop();
+        let mut seen = None;
+        loop {
+            let status = self.changed(seen).await;
+            if matches!(status.state, ServerState::Stopped | ServerState::Failed(_)) {
+                return;
+            }
+            seen = Some(status.generation);
+        }
+    }
+    /// kill the running process,the watcher spawns a new one right away
+    ///
+    /// also while it waits out the backoff after a crash
+    pub fn restart(&self) {
+        let mut inner = lock(&self.inner);
+        inner.want_running = true;
+        inner.restart_now = true;
+        if let Some(child) = inner.child.as_mut() {
+            let _ = child.kill();
+        }
+        drop(inner);
+        self.wake.notify_all();
+        self.start();
+    }
+}
+
+/// a panicked reader thread must not take the server page down with it
+fn lock(inner: &Mutex<Inner>) -> MutexGuard<'_, Inner> {
+    inner.lock().unwrap_or_else(|e| e.into_inner())
+}
+fn set_state(inner: &mut Inner, state: ServerState) {
+    inner.state = state;
+    inner.generation += 1;
+}
+fn push_line(shared: &Mutex<Inner>, line: LogLine) {
+    let mut inner = lock(shared);
+    if inner.log.len() == LOG_CAPACITY {
+        inner.log.pop_front();
+    }
+    inner.log.push_back(line);
+    inner.logged += 1;
+    inner.generation += 1;
+}
+
+/// worker thread: spawn,wait,restart with backoff while wanted
+fn watch(exe: PathBuf, workdir: PathBuf, shared: Arc<Mutex<Inner>>, wake: Arc<Condvar>) {
+    let mut backoff = BACKOFF_START;
+    let mut restarts = 0;
+    loop {
+        if !lock(&shared).want_running {
+            thread::sleep(POLL);
+            continue;
+        }
+        let mut child = match spawn(&exe, &workdir) {
+            Ok(child) => child,
+            Err(e) => {
+                let mut inner = lock(&shared);
+                inner.want_running = false;
+                set_state(&mut inner, ServerState::Failed(e.to_string()));
+                continue;
+            }
+        };
+        pump(child.stdout.take(), Stream::Stdout, shared.clone());
+        pump(child.stderr.take(), Stream::Stderr, shared.clone());
+        let started = Instant::now();
+        {
+            let mut inner = lock(&shared);
+            set_state(&mut inner, ServerState::Running { pid: child.id() });
+            // a restart asked for until now is this run
+            inner.restart_now = false;
+            // stop came while spawning
+            if !inner.want_running {
+                let _ = child.kill();
+            }
+            inner.child = Some(child);
+        }
+
+        let status = wait_child(&shared);
+        let mut inner = lock(&shared);
+        inner.child = None;
+        if !inner.want_running {
+            set_state(&mut inner, ServerState::Stopped);
+            continue;
+        }
+        if started.elapsed() >= STABLE_AFTER {
+            backoff = BACKOFF_START;
+            restarts = 0;
+        }
+        restarts += 1;
+        set_state(
+            &mut inner,
+            ServerState::Crashed {
+                code: status.and_then(|s| s.code()),
+                restarts,
+                retry_in: backoff,
+            },
+        );
+        drop(inner);
+        backoff = if wait_backoff(&shared, &wake, backoff) {
+            // the user is watching,start over from the shortest delay
+            BACKOFF_START
+        } else {
+            (backoff * 2).min(BACKOFF_MAX)
+        };
+    }
+}
+/// backup thread,results go to the log like ankisyncd's own output
+fn schedule_backups(shared: Arc<Mutex<Inner>>) {
+    loop {
+        if lock(&shared).want_running {
+            let line = match async_std::task::block_on(scheduled_backup()) {
+                None => None,
+                Some(Ok(archive)) => Some(LogLine {
+                    stream: Stream::Stdout,
+                    text: format!("deployer: backup written to {}", archive.path.display()),
+                }),
+                Some(Err(e)) => Some(LogLine {
+                    stream: Stream::Stderr,
+                    text: format!("deployer: backup failed: {}", e),
+                }),
+            };
+            if let Some(line) = line {
+                push_line(&shared, line);
+            }
+        }
+        thread::sleep(BACKUP_CHECK);
+    }
+}
+fn spawn(exe: &PathBuf, workdir: &PathBuf) -> std::io::Result<Child> {
+    let mut cmd = Command::new(exe);
+    cmd.current_dir(workdir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW,the wizard shows the output instead of a console
+        cmd.creation_flags(0x0800_0000);
+    }
+    cmd.spawn()
+}
+/// poll instead of wait so stop can kill the child meanwhile
+fn wait_child(shared: &Mutex<Inner>) -> Option<ExitStatus> {
+    loop {
+        {
+            let mut inner = lock(shared);
+            match inner.child.as_mut().map(|c| c.try_wait()) {
+                Some(Ok(Some(status))) => return Some(status),
+                Some(Ok(None)) => {}
+                Some(Err(_)) | None => return None,
+            }
+        }
+        thread::sleep(POLL);
+    }
+}
+/// sleep for total unless stopped or restarted meanwhile,true if restarted
+fn wait_backoff(shared: &Mutex<Inner>, wake: &Condvar, total: Duration) -> bool {
+    let until = Instant::now() + total;
+    let mut inner = lock(shared);
+    while inner.want_running && !inner.restart_now {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        inner = wake
+            .wait_timeout(inner, left)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+    std::mem::take(&mut inner.restart_now)
+}
+/// copy lines of a child pipe into the ring buffer
+fn pump<R: Read + Send + 'static>(pipe: Option<R>, stream: Stream, shared: Arc<Mutex<Inner>>) {
+    if let Some(pipe) = pipe {
+        thread::spawn(move || {
+            for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+                // ankisyncd on Windows may print in the console code page
+                let text = String::from_utf8_lossy(&line).trim_end().to_owned();
+                push_line(&shared, LogLine { stream, text });
+            }
+        });
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn wait_for(s: &Supervisor, f: impl Fn(&ServerStatus) -> bool) -> ServerStatus {
+        let until = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = s.status();
+            if f(&status) || Instant::now() > until {
+                return status;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn captures_output_and_restarts_after_crash() {
+        let dir = std::env::temp_dir().join(format!("deployer-sup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("fake-ankisyncd");
+        std::fs::write(&exe, "#!/bin/sh\necho listening\necho oops >&2\nexit 3\n").unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let s = Supervisor::new(exe, dir.clone());
+        s.start();
+        let status = wait_for(&s, |st| {
+            matches!(st.state, ServerState::Crashed { .. }) && st.log.len() >= 2
+        });
+        assert!(matches!(
+            status.state,
+            ServerState::Crashed {
+                code: Some(3),
+                restarts: 1,
+                ..
+            }
+        ));
+        assert!(status.log.contains(&LogLine {
+            stream: Stream::Stderr,
+            text: "oops".into()
+        }));
+
+        // restart cuts the 2s backoff after the second crash short
+        wait_for(&s, |st| {
+            matches!(st.state, ServerState::Crashed { restarts: 2, .. })
+        });
+        let asked = Instant::now();
+        s.restart();
+        let status = wait_for(&s, |st| {
+            matches!(st.state, ServerState::Crashed { restarts: 3, .. })
+        });
+        assert!(matches!(
+            status.state,
+            ServerState::Crashed { restarts: 3, .. }
+        ));
+        assert!(asked.elapsed() < Duration::from_millis(1500));
+
+        s.stop();
+        let status = wait_for(&s, |st| st.state == ServerState::Stopped);
+        assert_eq!(status.state, ServerState::Stopped);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use crate::anki::SyncTarget;
+use crate::cert_info::CertInfo;
+use crate::error::ApplicationError;
+use crate::interfaces::{lan_addrs, preferred};
+use async_std::channel::{self, Receiver, Sender};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// how often addresses are compared when there's no netlink
+pub static POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// DHCP and SLAAC fire several changes in a row,wait them out
+static SETTLE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// woken by the kernel on address and link changes
+    Netlink,
+    Polling,
+}
+/// what watch mode did,one JSON object per line in the console
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    Started {
+        backend: Backend,
+        /// host PC Anki syncs to
+        ip: Option<String>,
+    },
+    /// the preferred LAN address moved,None when there's none left
+    AddrChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// PC Anki points at the new address
+    ConfigUpdated { sync_url: String },
+    /// server cert lacks ip,logic::follow_addr reissues it right after
+    CertOutdated { ip: String },
+    /// server cert reissued for ip
+    CertRenewed { ip: String },
+    /// a step failed,watching goes on
+    Failed { step: String, error: String },
+}
+impl WatchEvent {
+    pub fn failed(step: &str, e: impl ToString) -> WatchEvent {
+        WatchEvent::Failed {
+            step: step.into(),
+            error: e.to_string(),
+        }
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// keeps PC Anki pointed at the preferred LAN address
+pub struct Watch {
+    target: SyncTarget,
+    /// interface the user picked,see interfaces::preferred
+    remembered: Option<String>,
+    /// server cert to check the new address against
+    cert_file: Option<PathBuf>,
+    current: Option<String>,
+    backend: Backend,
+    ticks: Receiver<()>,
+    /// false until the first check,which runs without waiting
+    checked: bool,
+}
+impl Watch {
+    pub async fn new(
+        target: SyncTarget,
+        remembered: Option<String>,
+        cert_file: Option<PathBuf>,
+    ) -> Watch {
+        let current = target.current().await.map(|a| a.host);
+        let (tx, ticks) = channel::unbounded();
+        let backend = spawn_notifier(tx);
+        Watch {
+            target,
+            remembered,
+            cert_file,
+            current,
+            backend,
+            ticks,
+            checked: false,
+        }
+    }
+    pub fn started(&self) -> WatchEvent {
+        WatchEvent::Started {
+            backend: self.backend,
+            ip: self.current.clone(),
+        }
+    }
+    /// wait for the preferred address to change,then follow it
+    ///
+    /// the first call follows right away if PC Anki is out of date
+    pub async fn next(&mut self) -> Vec<WatchEvent> {
+        loop {
+            if self.checked {
+                if self.ticks.recv().await.is_err() {
+                    // notifier died,fall back to polling
+                    let (tx, ticks) = channel::unbounded();
+                    spawn_poller(tx);
+                    self.ticks = ticks;
+                    self.backend = Backend::Polling;
+                    continue;
+                }
+                async_std::task::sleep(SETTLE).await;
+                while self.ticks.try_recv().is_ok() {}
+            }
+            self.checked = true;
+            let new = match lan_addrs() {
+                Ok(addrs) => {
+                    preferred(&addrs, self.remembered.as_deref()).map(|a| a.ip.to_string())
+                }
+                Err(e) => return vec![WatchEvent::failed("lan_addrs", e)],
+            };
+            if new != self.current {
+                return self.follow(new).await;
+            }
+        }
+    }
+    /// point PC Anki at new and check the server cert covers it
+    pub async fn follow(&mut self, new: Option<String>) -> Vec<WatchEvent> {
+        let mut events = vec![WatchEvent::AddrChanged {
+            old: self.current.clone(),
+            new: new.clone(),
+        }];
+        self.current = new.clone();
+        // offline,keep the old address until a new one shows up
+        let ip = match new {
+            Some(ip) => ip,
+            None => return events,
+        };
+        match self.update_config(&ip).await {
+            Ok(sync_url) => events.push(WatchEvent::ConfigUpdated { sync_url }),
+            Err(e) => events.push(WatchEvent::failed("config", e)),
+        }
+        if let Some(cert_file) = &self.cert_file {
+            match CertInfo::load(cert_file).await {
+                Ok(info) if info.covers(&ip) => {}
+                Ok(_) => events.push(WatchEvent::CertOutdated { ip }),
+                Err(e) => events.push(WatchEvent::failed("cert", e)),
+            }
+        }
+        events
+    }
+    /// keep scheme and port,only the host follows
+    async fn update_config(&self, ip: &str) -> Result<String, ApplicationError> {
+        let mut addr = (self.target.current().await)
+            .ok_or_else(|| ApplicationError::NoSyncAddr(self.target.path()))?;
+        addr.host = ip.into();
+        self.target.set(&addr).await?;
+        Ok(addr.sync_url())
+    }
+}
+
+/// netlink where the kernel has it,polling elsewhere
+fn spawn_notifier(tx: Sender<()>) -> Backend {
+    #[cfg(target_os = "linux")]
+    if let Ok(socket) = netlink::subscribe() {
+        thread::spawn(move || {
+            // ends with an error or once the watch is dropped
+            while netlink::wait(&socket).is_ok() {
+                if tx.try_send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        return Backend::Netlink;
+    }
+    spawn_poller(tx);
+    Backend::Polling
+}
+fn spawn_poller(tx: Sender<()>) {
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        if tx.try_send(()).is_err() {
+            return;
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use std::io;
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    /// rtnetlink socket in the link and address multicast groups
+    pub fn subscribe() -> io::Result<OwnedFd> {
+        // SAFETY: plain socket calls,the fd is owned right after creation
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = OwnedFd::from_raw_fd(fd);
+            let mut addr: libc::sockaddr_nl = mem::zeroed();
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups =
+                (libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+            let bound = libc::bind(
+                socket.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if bound < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(socket)
+        }
+    }
+    /// block until the kernel reports a change,its content doesn't matter
+    pub fn wait(socket: &OwnedFd) -> io::Result<()> {
+        let mut buf = [0u8; 8192];
+        // SAFETY: buf outlives the call and its length is passed along
+        let n = unsafe { libc::recv(socket.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if n < 0 {
+            let e = io::Error::last_os_error();
+            // a burst overflowed the socket buffer,still a change
+            if e.raw_os_error() == Some(libc::ENOBUFS) {
+                return Ok(());
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addon_config::AddonConfig;
+
+    #[test]
+    fn follows_new_address() {
+        async_std::task::block_on(follow_in_temp_dir());
+    }
+    async fn follow_in_temp_dir() {
+        let dir = std::env::temp_dir().join(format!("updateaddr-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let conf = r#"{"syncaddr": "https://192.168.1.2:27750/", "mediaaddr": ""}"#;
+        std::fs::write(AddonConfig::path_in(&dir), conf).unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["192.168.1.2".to_owned()]);
+        params.subject_alt_names = vec![rcgen::SanType::IpAddress("192.168.1.2".parse().unwrap())];
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+        let cert_file = dir.join("server.crt");
+        std::fs::write(&cert_file, cert.serialize_pem().unwrap()).unwrap();
+
+        let target = SyncTarget::Addon(dir.clone());
+        let mut watch = Watch::new(target, None, Some(cert_file)).await;
+        assert!(matches!(
+            watch.started(),
+            WatchEvent::Started { ip: Some(ip), .. } if ip == "192.168.1.2"
+        ));
+        let events = watch.follow(Some("10.0.0.5".into())).await;
+        assert_eq!(
+            events,
+            vec![
+                WatchEvent::AddrChanged {
+                    old: Some("192.168.1.2".into()),
+                    new: Some("10.0.0.5".into())
+                },
+                WatchEvent::ConfigUpdated {
+                    sync_url: "https://10.0.0.5:27750/".into()
+                },
+                WatchEvent::CertOutdated {
+                    ip: "10.0.0.5".into()
+                },
+            ]
+        );
+        assert_eq!(
+            events[0].to_json(),
+            r#"{"event":"addr_changed","old":"192.168.1.2","new":"10.0.0.5"}"#
+        );
+        // going offline leaves config.json alone
+        assert_eq!(watch.follow(None).await.len(), 1);
+        let back = AddonConfig::load(AddonConfig::path_in(&dir)).await.unwrap();
+        assert_eq!(back.sync_addr(), Some("https://10.0.0.5:27750/"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,757 @@
+use crate::error::ApplicationError;
+use clap::{Parser, Subcommand};
+use deploy_core::addon_config::{Scheme, SyncAddr};
+use deploy_core::diagnose::{Credentials, Outcome as CheckOutcome};
+use deploy_core::interfaces::lan_addrs;
+use deploy_core::logic::{
+    add_startmenu, addr_watch, addusr, anki_version, backup_now, bundle_path, chgepass, delusr,
+    deploy_plan, diagnose, export_bundle, follow_addr, import_to_sysstore, install_servcerts,
+    list_backups, list_config_backups, lookup_ip, lsusr, manifest_path, pcip_modify, remember_ip,
+    restore_backup, restore_config_backup, run_step, send_shortcut, set_port, sync_port,
+};
+use deploy_core::manifest::{Manifest, Outcome, StepKind};
+use deploy_core::platform::{self, Platform};
+use deploy_core::rollback::uninstall;
+use deploy_core::supervisor::{supervisor, ServerState, Stream};
+use serde_json::{json, Value};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// step finished
+pub static EXIT_OK: i32 = 0;
+/// step ran and failed
+pub static EXIT_FAILED: i32 = 1;
+// clap exits with 2 on bad arguments
+/// step can't run on this machine,e.g. PC Anki too old
+pub static EXIT_UNSUPPORTED: i32 = 3;
+/// plan shown and not confirmed,nothing ran
+pub static EXIT_DECLINED: i32 = 4;
+
+/// anki sync server deployer,run without arguments to open the wizard
+#[derive(Parser, Debug)]
+#[clap(name = "deployer", version)]
+pub struct Cli {
+    /// print results as JSON
+    #[clap(long, global = true)]
+    json: bool,
+    /// dir holding pre,the executable's dir by default
+    #[clap(long, global = true)]
+    root: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Cmd>,
+}
+impl Cli {
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+    /// a step to run,else the wizard opens
+    pub fn is_headless(&self) -> bool {
+        self.command.is_some()
+    }
+}
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// check PC Anki version is above 2.1.10 and how it takes the sync server
+    DetectAnki,
+    /// point PC Anki at ip,copying the ankisyncd addon if its version needs it
+    SetIp {
+        /// LAN ip address,looked up if omitted
+        ip: Option<String>,
+    },
+    /// move ankisyncd to port,fails if another process listens on it
+    SetPort { port: u16 },
+    /// issue server cert for ip and enable ssl in Settings.toml
+    InstallCerts {
+        /// LAN ip address,looked up if omitted
+        ip: Option<String>,
+    },
+    /// import root CA to system trust store and copy rootCA.crt to desktop
+    ImportCa,
+    /// send anki_server shortcut to desktop
+    Shortcut,
+    /// add anki_server to start menu
+    StartMenu,
+    /// show every file and system change the deployment steps would make,without making them
+    ///
+    /// with --apply the steps run once the plan is confirmed
+    Plan {
+        /// LAN ip address,looked up if omitted
+        ip: Option<String>,
+        /// step to plan,as named by status --json,every step if omitted
+        #[clap(long = "step", value_parser = parse_step)]
+        steps: Vec<StepKind>,
+        /// also write the plan to this file
+        #[clap(long)]
+        out: Option<std::path::PathBuf>,
+        /// run the steps after asking for confirmation
+        #[clap(long)]
+        apply: bool,
+        /// apply without asking
+        #[clap(long, requires = "apply")]
+        yes: bool,
+    },
+    /// list LAN addresses to choose from,the one set-ip would use first
+    Interfaces,
+    /// show which deployment steps are done
+    Status,
+    /// revert every deployment step,report what couldn't be reverted
+    Uninstall,
+    /// run ankisyncd in the foreground,restart it on crash and print its output
+    Serve,
+    /// follow LAN address changes until killed,print one JSON event per line
+    ///
+    /// updates PC Anki's sync address and reissues the server cert
+    Watch,
+    /// check a phone can reach and sync with ankisyncd
+    Diagnose {
+        /// LAN ip address,looked up if omitted
+        ip: Option<String>,
+        // test account for the hostKey and media begin checks
+        #[clap(flatten)]
+        account: AccountArgs,
+    },
+    /// write rootCA.crt and a page with the sync urls,QR codes and setup steps for clients
+    ExportBundle {
+        /// LAN ip address,looked up if omitted
+        ip: Option<String>,
+        /// dir or zip to write,on the desktop if omitted
+        #[clap(long)]
+        out: Option<std::path::PathBuf>,
+        /// write a zip instead of a dir
+        #[clap(long)]
+        zip: bool,
+        // account to put on the page,its password in plain text
+        #[clap(flatten)]
+        account: AccountArgs,
+    },
+    /// manage sync accounts
+    #[clap(subcommand)]
+    User(UserCmd),
+    /// archive ankisyncd's collections and dbs,restore them
+    #[clap(subcommand)]
+    Backup(BackupCmd),
+    /// earlier versions of Settings.toml and the addon's config.json
+    #[clap(subcommand)]
+    ConfigBackup(ConfigBackupCmd),
+}
+#[derive(Subcommand, Debug)]
+enum UserCmd {
+    /// create an account,its password is asked for
+    Add {
+        name: String,
+        #[clap(flatten)]
+        password: PasswordArgs,
+    },
+    /// list account names
+    List,
+    /// delete an account
+    Delete { name: String },
+    /// change password of an account,the new one is asked for
+    Passwd {
+        name: String,
+        #[clap(flatten)]
+        password: PasswordArgs,
+    },
+}
+/// where user add and passwd take the password from
+///
+/// never from the command line,which ends up in shell history and ps
+#[derive(clap::Args, Debug)]
+struct PasswordArgs {
+    /// read the password from the first line of stdin,for scripts
+    #[clap(long)]
+    password_stdin: bool,
+    /// password as an argument,deprecated,kept for old scripts and left out of --help
+    #[clap(hide = true, conflicts_with = "password-stdin")]
+    password: Option<String>,
+}
+impl PasswordArgs {
+    fn read(self) -> std::io::Result<String> {
+        match self.password {
+            Some(password) => {
+                eprintln!(
+                    "warning: a password as an argument is deprecated,it ends up in shell \
+                     history and ps,use --password-stdin or the prompt"
+                );
+                Ok(password)
+            }
+            None => read_password(self.password_stdin, true),
+        }
+    }
+}
+/// an existing sync account,its password is asked for
+#[derive(clap::Args, Debug)]
+struct AccountArgs {
+    /// account name
+    #[clap(long)]
+    user: Option<String>,
+    /// read the account's password from the first line of stdin,for scripts
+    #[clap(long, requires = "user")]
+    password_stdin: bool,
+}
+impl AccountArgs {
+    fn read(self) -> std::io::Result<Option<Credentials>> {
+        let username = match self.user {
+            Some(username) => username,
+            None => return Ok(None),
+        };
+        let password = read_password(self.password_stdin, false)?;
+        Ok(Some(Credentials { username, password }))
+    }
+}
+#[derive(Subcommand, Debug)]
+enum BackupCmd {
+    /// write a new archive to pre/backups and drop expired ones
+    Create,
+    /// list archives,newest first
+    List,
+    /// stop ankisyncd,verify archive and swap its data in
+    Restore { archive: std::path::PathBuf },
+}
+#[derive(Subcommand, Debug)]
+enum ConfigBackupCmd {
+    /// list backups of every config file,newest first
+    List,
+    /// write a backup over the file it was taken of
+    Restore { backup: std::path::PathBuf },
+}
+/// outcome of one step
+struct Report {
+    command: &'static str,
+    code: i32,
+    message: String,
+    data: Value,
+}
+
+/// parse args,on the parent's console if there are any so --help shows
+pub fn parse() -> Cli {
+    if std::env::args_os().len() > 1 {
+        attach_console();
+    }
+    Cli::parse()
+}
+/// run the step and return process exit code
+pub fn run(cli: Cli) -> i32 {
+    let command = cli.command.expect("headless mode has a step");
+    let platform = platform::current();
+    let report = match async_std::task::block_on(execute(platform, command)) {
+        Ok(report) => report,
+        Err((command, e)) => Report {
+            command,
+            code: EXIT_FAILED,
+            message: e.to_string(),
+            data: Value::Null,
+        },
+    };
+    if cli.json {
+        let out = json!({
+            "command": report.command,
+            "ok": report.code == EXIT_OK,
+            "exit_code": report.code,
+            "message": report.message,
+            "data": report.data,
+        });
+        println!("{}", out);
+    } else if report.code == EXIT_OK {
+        println!("{}", report.message);
+    } else {
+        eprintln!("{}: {}", report.command, report.message);
+    }
+    report.code
+}
+
+async fn execute(
+    platform: &dyn Platform,
+    cmd: Cmd,
+) -> Result<Report, (&'static str, ApplicationError)> {
+    let name = cmd.name();
+    let fail = |e| (name, e);
+    let ok = |message: String, data: Value| Report {
+        command: name,
+        code: EXIT_OK,
+        message,
+        data,
+    };
+    let report = match cmd {
+        Cmd::DetectAnki => {
+            let version = anki_version(platform).map_err(fail)?;
+            let supported = version.is_supported();
+            let builtin = version.has_builtin_sync();
+            Report {
+                code: if supported { EXIT_OK } else { EXIT_UNSUPPORTED },
+                ..ok(
+                    if !supported {
+                        format!("PC Anki {} is older than 2.1.11", version)
+                    } else if builtin {
+                        format!("PC Anki {} OK,sync server set in its preferences", version)
+                    } else {
+                        format!(
+                            "PC Anki {} OK,sync server set by the ankisyncd addon",
+                            version
+                        )
+                    },
+                    json!({
+                        "version": version.to_string(),
+                        "supported": supported,
+                        "builtin_sync": builtin,
+                    }),
+                )
+            }
+        }
+        Cmd::SetIp { ip } => {
+            let ip = ip_or_lookup(ip).await.map_err(fail)?;
+            pcip_modify(platform, &ip).await.map_err(fail)?;
+            let addr = SyncAddr::new(Scheme::Https, &ip, sync_port().await.map_err(fail)?);
+            ok(
+                format!("sync address set to {}", addr.base_url()),
+                json!({ "ip": ip, "port": addr.port }),
+            )
+        }
+        Cmd::SetPort { port } => {
+            set_port(platform, port).await.map_err(fail)?;
+            ok(
+                format!("sync port set to {}", port),
+                json!({ "port": port }),
+            )
+        }
+        Cmd::InstallCerts { ip } => {
+            let ip = ip_or_lookup(ip).await.map_err(fail)?;
+            install_servcerts(platform, &ip).await.map_err(fail)?;
+            ok(
+                format!("server cert issued for {}", ip),
+                json!({ "ip": ip }),
+            )
+        }
+        Cmd::ImportCa => {
+            import_to_sysstore(platform).await.map_err(fail)?;
+            ok("root CA imported".into(), Value::Null)
+        }
+        Cmd::Shortcut => {
+            send_shortcut(platform).await.map_err(fail)?;
+            ok("shortcut sent to desktop".into(), Value::Null)
+        }
+        Cmd::StartMenu => {
+            add_startmenu(platform).await.map_err(fail)?;
+            ok("start menu entry added".into(), Value::Null)
+        }
+        Cmd::Plan {
+            ip,
+            steps,
+            out,
+            apply,
+            yes,
+        } => {
+            // a dry run must not even remember the ip
+            let ip = match ip {
+                Some(ip) if apply => ip_or_lookup(Some(ip)).await.map_err(fail)?,
+                Some(ip) => ip,
+                None => lookup_ip().await.map_err(fail)?,
+            };
+            let steps: Vec<StepKind> = StepKind::all()
+                .into_iter()
+                .filter(|s| steps.is_empty() || steps.contains(s))
+                .collect();
+            let plan = deploy_plan(platform, &steps, &ip).await.map_err(fail)?;
+            if let Some(out) = &out {
+                plan.save(out).await.map_err(fail)?;
+            }
+            if !apply {
+                return Ok(ok(plan.to_string(), json!(plan)));
+            }
+            if !yes && !confirm(&plan.to_string()).map_err(|e| fail(e.into()))? {
+                return Ok(Report {
+                    code: EXIT_DECLINED,
+                    ..ok("not applied".into(), json!(plan))
+                });
+            }
+            for step in &steps {
+                run_step(platform, *step, &ip).await.map_err(fail)?;
+            }
+            ok(
+                format!("{} steps applied for {}", steps.len(), ip),
+                json!(plan),
+            )
+        }
+        Cmd::Interfaces => {
+            let chosen = lookup_ip().await.ok();
+            let addrs = lan_addrs().map_err(|e| fail(e.into()))?;
+            let lines: Vec<String> = addrs
+                .iter()
+                .map(|a| {
+                    let mark = if chosen.as_deref() == Some(&a.ip.to_string()) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    format!("{} {}", mark, a)
+                })
+                .collect();
+            let data: Vec<Value> = addrs
+                .iter()
+                .map(|a| {
+                    json!({
+                        "name": a.name,
+                        "ip": a.ip.to_string(),
+                        "flags": a.flags.labels(),
+                    })
+                })
+                .collect();
+            ok(
+                lines.join("\n"),
+                json!({ "chosen": chosen, "interfaces": data }),
+            )
+        }
+        Cmd::Status => {
+            let manifest = Manifest::load(&manifest_path()).await.map_err(fail)?;
+            let lines: Vec<String> = StepKind::all()
+                .iter()
+                .map(|step| match manifest.last(*step) {
+                    Some(r) => match &r.outcome {
+                        Outcome::Ok => format!("{:?}: ok {}", step, r.timestamp),
+                        Outcome::Failed { error } => format!("{:?}: failed {}", step, error),
+                    },
+                    None => format!("{:?}: not run", step),
+                })
+                .collect();
+            ok(
+                lines.join("\n"),
+                json!({
+                    "deploy_count": manifest.deploy_count,
+                    "first_incomplete": manifest.first_incomplete(),
+                    "steps": StepKind::all()
+                        .iter()
+                        .map(|step| manifest.last(*step))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+        }
+        Cmd::Uninstall => {
+            let report = uninstall(platform).await.map_err(fail)?;
+            let mut lines: Vec<String> = report
+                .reverted
+                .iter()
+                .map(|a| format!("ok: {}", a))
+                .collect();
+            lines.extend(
+                report
+                    .failed
+                    .iter()
+                    .map(|(a, e)| format!("failed: {}: {}", a, e)),
+            );
+            Report {
+                code: if report.is_clean() {
+                    EXIT_OK
+                } else {
+                    EXIT_FAILED
+                },
+                ..ok(lines.join("\n"), json!(report))
+            }
+        }
+        Cmd::Serve => {
+            let server = supervisor();
+            server.start();
+            let (mut seen, mut printed) = (None, 0);
+            // runs until killed,returns only when ankisyncd can't be spawned
+            loop {
+                let status = server.changed(seen).await;
+                seen = Some(status.generation);
+                let new = (status.logged - printed).min(status.log.len() as u64) as usize;
+                printed = status.logged;
+                for line in &status.log[status.log.len() - new..] {
+                    match line.stream {
+                        Stream::Stdout => println!("{}", line.text),
+                        Stream::Stderr => eprintln!("{}", line.text),
+                    }
+                }
+                match status.state {
+                    ServerState::Failed(_) => {
+                        return Err(fail(ApplicationError::CommandFailed(
+                            "ankisyncd".into(),
+                            status.state.to_string(),
+                        )))
+                    }
+                    ServerState::Crashed { .. } => eprintln!("ankisyncd {}", status.state),
+                    _ => {}
+                }
+            }
+        }
+        Cmd::Watch => {
+            let mut watch = addr_watch(platform).await.map_err(fail)?;
+            println!("{}", watch.started().to_json());
+            loop {
+                for event in follow_addr(platform, &mut watch).await {
+                    println!("{}", event.to_json());
+                }
+            }
+        }
+        Cmd::Diagnose { ip, account } => {
+            let ip = match ip {
+                Some(ip) => ip,
+                None => lookup_ip().await.map_err(fail)?,
+            };
+            let credentials = account.read().map_err(|e| fail(e.into()))?;
+            let results = diagnose(ip, credentials).await.map_err(fail)?;
+            let lines: Vec<String> = results
+                .iter()
+                .map(|r| match &r.outcome {
+                    CheckOutcome::Ok => format!("{}: ok", r.check.name()),
+                    CheckOutcome::Failed(problem) => {
+                        format!("{}: failed: {}", r.check.name(), problem)
+                    }
+                    CheckOutcome::Skipped => format!("{}: skipped", r.check.name()),
+                })
+                .collect();
+            let data: Vec<Value> = results
+                .iter()
+                .map(|r| {
+                    let (status, message) = match &r.outcome {
+                        CheckOutcome::Ok => ("ok", None),
+                        CheckOutcome::Failed(problem) => ("failed", Some(problem.to_string())),
+                        CheckOutcome::Skipped => ("skipped", None),
+                    };
+                    json!({ "check": r.check.name(), "status": status, "message": message })
+                })
+                .collect();
+            let failed = results
+                .iter()
+                .any(|r| matches!(r.outcome, CheckOutcome::Failed(_)));
+            Report {
+                code: if failed { EXIT_FAILED } else { EXIT_OK },
+                ..ok(lines.join("\n"), json!({ "checks": data }))
+            }
+        }
+        Cmd::ExportBundle {
+            ip,
+            out,
+            zip,
+            account,
+        } => {
+            let ip = match ip {
+                Some(ip) => ip,
+                None => lookup_ip().await.map_err(fail)?,
+            };
+            let out = match out {
+                Some(out) => out,
+                None => bundle_path(platform, zip).map_err(fail)?,
+            };
+            let account = account.read().map_err(|e| fail(e.into()))?;
+            let path = export_bundle(&ip, out, zip, account).await.map_err(fail)?;
+            ok(
+                format!("client bundle written to {}", path.display()),
+                json!({ "path": path }),
+            )
+        }
+        Cmd::User(UserCmd::Add { name, password }) => {
+            let password = password.read().map_err(|e| fail(e.into()))?;
+            addusr(&name, &password).await.map_err(fail)?;
+            ok(format!("user {} added", name), json!({ "name": name }))
+        }
+        Cmd::User(UserCmd::List) => {
+            let users = lsusr().await.map_err(fail)?;
+            let users: Vec<&str> = users.iter().map(|a| a.username.as_str()).collect();
+            ok(users.join("\n"), json!({ "users": users }))
+        }
+        Cmd::User(UserCmd::Delete { name }) => {
+            delusr(&name).await.map_err(fail)?;
+            ok(format!("user {} deleted", name), json!({ "name": name }))
+        }
+        Cmd::User(UserCmd::Passwd { name, password }) => {
+            let password = password.read().map_err(|e| fail(e.into()))?;
+            chgepass(&name, &password).await.map_err(fail)?;
+            ok(
+                format!("password of {} changed", name),
+                json!({ "name": name }),
+            )
+        }
+        Cmd::Backup(BackupCmd::Create) => {
+            let archive = backup_now().await.map_err(fail)?;
+            ok(
+                format!("backup written to {}", archive.path.display()),
+                json!({ "path": archive.path, "size": archive.size }),
+            )
+        }
+        Cmd::Backup(BackupCmd::List) => {
+            let archives = list_backups().await.map_err(fail)?;
+            let lines: Vec<String> = archives
+                .iter()
+                .map(|a| format!("{} {} bytes", a.path.display(), a.size))
+                .collect();
+            let data: Vec<Value> = archives
+                .iter()
+                .map(|a| json!({ "path": a.path, "size": a.size, "created": a.created.unix_timestamp() }))
+                .collect();
+            ok(lines.join("\n"), json!({ "backups": data }))
+        }
+        Cmd::Backup(BackupCmd::Restore { archive }) => {
+            let saved = restore_backup(platform, archive).await.map_err(fail)?;
+            ok(
+                format!("restored,replaced data moved to {}", saved.display()),
+                json!({ "replaced": saved }),
+            )
+        }
+        Cmd::ConfigBackup(ConfigBackupCmd::List) => {
+            let found = list_config_backups(platform).await.map_err(fail)?;
+            let lines: Vec<String> = found
+                .iter()
+                .map(|b| format!("{} {} bytes", b.path.display(), b.size))
+                .collect();
+            let data: Vec<Value> = found
+                .iter()
+                .map(|b| json!({ "path": b.path, "original": b.original, "size": b.size, "created": b.created.unix_timestamp() }))
+                .collect();
+            ok(lines.join("\n"), json!({ "backups": data }))
+        }
+        Cmd::ConfigBackup(ConfigBackupCmd::Restore { backup }) => {
+            // matched by name,the path may be given relative to another dir
+            let found = list_config_backups(platform)
+                .await
+                .map_err(fail)?
+                .into_iter()
+                .find(|b| b.path.file_name() == backup.file_name())
+                .ok_or(ApplicationError::NotConfigBackup(backup))
+                .map_err(fail)?;
+            let file = restore_config_backup(found).await.map_err(fail)?;
+            ok(
+                format!("{} restored", file.display()),
+                json!({ "restored": file }),
+            )
+        }
+    };
+    Ok(report)
+}
+
+impl Cmd {
+    fn name(&self) -> &'static str {
+        match self {
+            Cmd::DetectAnki => "detect-anki",
+            Cmd::SetIp { .. } => "set-ip",
+            Cmd::SetPort { .. } => "set-port",
+            Cmd::InstallCerts { .. } => "install-certs",
+            Cmd::ImportCa => "import-ca",
+            Cmd::Shortcut => "shortcut",
+            Cmd::StartMenu => "start-menu",
+            Cmd::Plan { .. } => "plan",
+            Cmd::Interfaces => "interfaces",
+            Cmd::Status => "status",
+            Cmd::Uninstall => "uninstall",
+            Cmd::Serve => "serve",
+            Cmd::Watch => "watch",
+            Cmd::Diagnose { .. } => "diagnose",
+            Cmd::ExportBundle { .. } => "export-bundle",
+            Cmd::User(UserCmd::Add { .. }) => "user add",
+            Cmd::User(UserCmd::List) => "user list",
+            Cmd::User(UserCmd::Delete { .. }) => "user delete",
+            Cmd::User(UserCmd::Passwd { .. }) => "user passwd",
+            Cmd::Backup(BackupCmd::Create) => "backup create",
+            Cmd::Backup(BackupCmd::List) => "backup list",
+            Cmd::Backup(BackupCmd::Restore { .. }) => "backup restore",
+            Cmd::ConfigBackup(ConfigBackupCmd::List) => "config-backup list",
+            Cmd::ConfigBackup(ConfigBackupCmd::Restore { .. }) => "config-backup restore",
+        }
+    }
+}
+
+/// an ip given on the command line becomes the remembered choice
+async fn ip_or_lookup(ip: Option<String>) -> Result<String, ApplicationError> {
+    match ip {
+        Some(ip) => {
+            remember_ip(ip.clone()).await?;
+            Ok(ip)
+        }
+        None => lookup_ip().await,
+    }
+}
+
+/// step name as status --json prints it,e.g. certs_issued
+fn parse_step(name: &str) -> Result<StepKind, String> {
+    serde_json::from_value(Value::String(name.into())).map_err(|_| {
+        let names: Vec<Value> = StepKind::all().iter().map(|s| json!(s)).collect();
+        format!("expected one of {}", Value::Array(names))
+    })
+}
+/// show plan on stderr,stdout stays clean for --json,and ask y/N
+fn confirm(plan: &str) -> std::io::Result<bool> {
+    eprint!("{}\napply these changes? [y/N] ", plan);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+/// a line of stdin with from_stdin,else asked for on the terminal without echo,
+/// twice with confirm
+fn read_password(from_stdin: bool, confirm: bool) -> std::io::Result<String> {
+    let interactive = !from_stdin && std::io::stdin().is_terminal();
+    let password = if interactive {
+        let password = prompt_hidden("password: ")?;
+        if confirm && prompt_hidden("retype password: ")? != password {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "passwords don't match",
+            ));
+        }
+        password
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    };
+    if password.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "empty password",
+        ));
+    }
+    Ok(password)
+}
+/// show prompt on stderr and read a line with echo turned off
+fn prompt_hidden(prompt: &str) -> std::io::Result<String> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    set_echo(false);
+    let mut line = String::new();
+    let read = std::io::stdin().lock().read_line(&mut line);
+    // echo comes back even if reading failed
+    set_echo(true);
+    eprintln!();
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+}
+#[cfg(windows)]
+fn set_echo(on: bool) {
+    use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_INPUT_HANDLE;
+    use winapi::um::wincon::ENABLE_ECHO_INPUT;
+    unsafe {
+        let stdin = GetStdHandle(STD_INPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(stdin, &mut mode) != 0 {
+            let mode = if on {
+                mode | ENABLE_ECHO_INPUT
+            } else {
+                mode & !ENABLE_ECHO_INPUT
+            };
+            SetConsoleMode(stdin, mode);
+        }
+    }
+}
+/// stty changes the terminal stdin is attached to
+#[cfg(not(windows))]
+fn set_echo(on: bool) {
+    let _ = std::process::Command::new("stty")
+        .arg(if on { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status();
+}
+
+/// the exe is built for the windows subsystem and has no console,
+/// borrow the one of the shell it was started from
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+#[cfg(not(windows))]
+fn attach_console() {}
@@ -1,19 +1,5 @@
-use thiserror::Error;
-#[derive(Error, Debug)]
-pub enum ApplicationError {
-    #[error("IO error {0}")]
-    IO(#[from] std::io::Error),
-    #[error("Iced error {0}")]
-    Iced(#[from] iced::Error),
-    #[error("Parse Int error {0}")]
-    ParseInt(#[from] std::num::ParseIntError),
-    #[error("from utf8 error {0}")]
-    FromUtf8(#[from] std::string::FromUtf8Error),
-    #[error("unknown data store error")]
-    Unknown,
-    // #[error("Missing values in parameter: {0}")]
-    // MissingValues(String),
-}
+/// one error type for every deployment operation,see deploy_core::error
+pub use deploy_core::error::ApplicationError;
 
 #[derive(Debug, Clone)]
 pub enum LoadError {
@@ -24,6 +10,8 @@ pub enum LoadError {
 #[derive(Debug, Clone)]
 pub enum CMDError {
     ExcError,
+    /// message of the ApplicationError behind it
+    Failed(String),
 }
 
 #[derive(Debug, Clone)]
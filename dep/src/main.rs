@@ -1,14 +1,41 @@
 #![windows_subsystem = "windows"]
+use deploy_core::i18n;
+use deploy_core::logic::manifest_path;
+use deploy_core::manifest::Manifest;
+use deploy_core::platform;
+use deploy_core::root::{self, DeploymentRoot};
 use iced::{Application, Settings};
+mod cli;
 mod error;
-mod logic;
 mod ui;
-use crate::ui::App;
-use error::ApplicationError;
-fn main() -> Result<(), ApplicationError> {
+mod wizard;
+use crate::ui::{App, CJK_FONT_BYTES};
+fn main() -> iced::Result {
+    let args = cli::parse();
+    // resolved once,every path below is absolute
+    let resolved = match args.root() {
+        Some(dir) => DeploymentRoot::new(dir),
+        None => DeploymentRoot::from_exe(),
+    };
+    match resolved {
+        Ok(dir) => root::init(dir),
+        Err(e) => {
+            eprintln!("can't resolve the deployment root: {}", e);
+            std::process::exit(cli::EXIT_FAILED);
+        }
+    };
+    // a step means headless mode,see deployer --help
+    if args.is_headless() {
+        std::process::exit(cli::run(args));
+    }
+    // the language picked in the wizard last time,else the system locale's
+    let chosen = async_std::task::block_on(Manifest::load(&manifest_path()))
+        .ok()
+        .and_then(|m| m.lang);
+    let lang = i18n::init(platform::current(), chosen.as_deref());
     App::run(Settings {
-        default_font: Some(include_bytes!("../fonts/simhei.ttf")),
+        // simhei only when the text needs CJK glyphs
+        default_font: lang.needs_cjk().then_some(CJK_FONT_BYTES),
         ..Default::default()
-    })?;
-    Ok(())
+    })
 }
@@ -1,34 +1,78 @@
 use std::default;
 
-use crate::{
-    error::{ApplicationError, CMDError, LoadError},
+use crate::error::{ApplicationError, CMDError, LoadError};
+use crate::wizard::{Page, Progress};
+use async_std::channel::{unbounded, Receiver};
+use async_std::task::JoinHandle;
+use deploy_core::addon_config::{Scheme, SyncAddr, DEFAULT_SYNC_PORT};
+use deploy_core::anki::AnkiVersion;
+use deploy_core::cert_info::CertInfo;
+use deploy_core::i18n::{lang, set_lang, tr, trf, Lang};
+use deploy_core::interfaces::{lan_addrs, LanAddr};
+use deploy_core::qr::phone_urls;
+use deploy_core::watch::WatchEvent;
+use deploy_core::{
+    backup::Archive,
+    ca::ROOTCA_FILE,
+    ca_share::{rootca_url, serve_rootca},
+    diagnose::{Check, CheckResult, Credentials, Outcome, Problem},
     logic::{
-        add_startmenu, addusr, async_read_to_string, chgepass, delusr, deploy_count_path,
-        import_to_sysstore, install_servcerts, lookup_ip, lsusr, pc_ver_required, pcip_modify,
-        read_parse_deploy_count, root_dir, send_shortcut,
+        addr_watch, addusr, anki_version, backup_now, bundle_path, ca_dir, chgepass, delusr,
+        deploy_plan, diagnose, export_bundle, follow_addr, list_backups, list_config_backups,
+        lookup_ip, lsusr, manifest_path, plan_path, remember_ip, remember_lang, renew_servcerts,
+        restore_backup, restore_config_backup, root_dir, run_step, server_cert_info, set_port,
+        step_log_path, sync_port,
     },
+    manifest::{Manifest, StepKind},
+    plan::{Operation, Plan},
+    platform::{self, Platform},
+    rollback::{uninstall, RollbackReport},
+    safe_write::FileBackup,
+    step_log::{self, LogLine, Source},
+    supervisor::{supervisor, ServerState, ServerStatus, Stream, LOG_CAPACITY},
 };
 use iced::{
-    alignment, button, executor, futures::FutureExt, text_input, Application, Button, Checkbox,
-    Color, Column, Command, Container, Element, Length, Radio, Row, Settings, Space, Subscription,
-    Text, TextInput, Toggler,
+    alignment, button, executor, futures::FutureExt, qr_code, scrollable, text_input, Application,
+    Button, Checkbox, Color, Column, Command, Container, Element, Font, Length, QRCode, Radio, Row,
+    Scrollable, Settings, Space, Subscription, Text, TextInput, Toggler,
 };
 use iced_native::{
     futures::{self, channel::mpsc, StreamExt},
     subscription,
 };
+/// simhei,the default font when the language needs CJK glyphs
+pub static CJK_FONT_BYTES: &[u8] = include_bytes!("../fonts/simhei.ttf");
+/// for CJK text while another default font is loaded
+static CJK_FONT: Font = Font::External {
+    name: "simhei",
+    bytes: CJK_FONT_BYTES,
+};
+/// output lines shown on the server page
+static SERVER_LOG_LINES: usize = 15;
+/// newest archives offered for restore on the server page
+static BACKUP_ROWS: usize = 5;
+/// height of the plan list before it scrolls
+static PLAN_HEIGHT: u32 = 220;
+/// latest address watch events shown on the server page
+static WATCH_EVENT_LINES: usize = 5;
+/// height of the step log before it scrolls
+static STEP_LOG_HEIGHT: u32 = 200;
 pub enum App {
     Loading,
     Loaded(State),
 }
-// merge Step and Message
 #[derive(Debug)]
 pub struct State {
+    platform: &'static dyn Platform,
     verok: bool,
-    deploy_count: u8,
+    /// None if PC Anki wasn't found
+    anki_version: Option<AnkiVersion>,
+    deploy_count: u32,
     back_button: button::State,
     next_button: button::State,
-    current: u8,
+    page: Page,
+    /// steps an earlier run finished for ipaddr,forgotten once they're redone
+    done_before: Vec<StepKind>,
     ///welcome
     username: String,
     password: String,
@@ -39,6 +83,18 @@ pub struct State {
 
     submit_button: button::State,
     useracnt_optips: String,
+    /// server cert configured in Settings.toml,None before first deploy
+    cert_info: Option<CertInfo>,
+    renew_button: button::State,
+    cert_renewing: bool,
+    /// why the last reissue failed
+    cert_message: Option<String>,
+    /// some step was done,there is something to uninstall
+    deployed: bool,
+    uninstall_button: button::State,
+    uninstall_confirming: bool,
+    uninstalling: bool,
+    uninstall_report: Option<RollbackReport>,
 
     // Set ip
     pcip_modified: bool,
@@ -47,27 +103,104 @@ pub struct State {
     ///checkbox
     send_rootca_to_phone: bool,
     ipaddr: String,
+    /// sync port from Settings.toml
+    port: u16,
+    port_input: String,
+    port_state: text_input::State,
+    port_button: button::State,
+    /// port taken by another process or not a number
+    port_error: Option<String>,
+    /// LAN addresses to choose from,likeliest first
+    lan_addrs: Vec<LanAddr>,
+    /// label and code of sync,media and rootCA urls for ipaddr
+    qr_codes: Vec<(String, qr_code::State)>,
+    /// rootCA download server couldn't start,e.g. port in use
+    rootca_share_error: Option<String>,
     state_sync: text_input::State,
     state_media: text_input::State,
-    ready_for_pcmod: bool,
-    ready_for_certsin: bool,
-    ready_for_sysstore: bool,
     ready_for_lookup: bool,
 
     // shortcut to desktop
     shortcut_sent: bool,
     search_enable: bool,
-    ready_for_shortcut: bool,
-    ready_for_search: bool,
+
+    /// step of the set ip or shortcut page being run,it starts the next when done
+    running: Option<StepKind>,
+
+    // server
+    server_status: ServerStatus,
+    server_start_button: button::State,
+    server_stop_button: button::State,
+    server_restart_button: button::State,
+    /// optional test account for the hostKey and media checks
+    diag_user: String,
+    diag_pass: String,
+    diag_user_state: text_input::State,
+    diag_pass_state: text_input::State,
+    diagnose_button: button::State,
+    diagnosing: bool,
+    diag_results: Vec<CheckResult>,
+    diag_error: Option<String>,
+    /// newest first,each with its restore button
+    backups: Vec<(Archive, button::State)>,
+    backup_button: button::State,
+    backing_up: bool,
+    /// archive whose restore button was pressed once
+    restore_confirming: Option<usize>,
+    restoring: bool,
+    /// outcome of the last backup or restore,true if it failed
+    backup_message: Option<(String, bool)>,
+    /// earlier versions of Settings.toml and config.json,newest first
+    config_backups: Vec<(FileBackup, button::State)>,
+    config_restore_confirming: Option<usize>,
+    config_restoring: bool,
+    config_message: Option<(String, bool)>,
+    /// follow LAN address changes while the wizard is open
+    watch_addr: bool,
+    watch_events: Vec<WatchEvent>,
+    /// last account created or given a new password,the only one whose password is known
+    bundle_account: Option<Credentials>,
+    bundle_with_account: bool,
+    bundle_zip: bool,
+    bundle_button: button::State,
+    exporting: bool,
+    /// where the bundle went or why it failed,true if it failed
+    bundle_message: Option<(String, bool)>,
+    /// simhei was loaded as the default font at startup
+    cjk_font: bool,
+    /// picked but needs simhei,applied on the next start
+    lang_pending: Option<Lang>,
+    /// page whose steps wait for the plan to be applied
+    plan_pending: Option<Page>,
+    /// changes of the pending page's steps,None while they're worked out
+    plan: Option<Plan>,
+    /// export result or why planning failed,true if it's an error
+    plan_message: Option<(String, bool)>,
+    plan_scroll: scrollable::State,
+    apply_button: button::State,
+    plan_export_button: button::State,
+    /// lines the steps logged this session,oldest first
+    step_log: Vec<LogLine>,
+    /// error of each step whose latest run failed
+    step_errors: Vec<(StepKind, String)>,
+    log_expanded: bool,
+    /// where the log was saved or why it couldn't be,true if it's an error
+    log_message: Option<(String, bool)>,
+    log_scroll: scrollable::State,
+    log_toggle_button: button::State,
+    log_save_button: button::State,
 }
 impl Default for State {
     fn default() -> Self {
         State {
+            platform: platform::current(),
             verok: false,
+            anki_version: None,
             deploy_count: 0,
             back_button: button::State::new(),
             next_button: button::State::new(),
-            current: 0,
+            page: Page::Welcome,
+            done_before: Vec::new(),
 
             useracnt_optips: String::new(),
 
@@ -78,316 +211,208 @@ impl Default for State {
             selection: None,
             list_radio_enable: false,
             submit_button: button::State::new(),
+            cert_info: None,
+            renew_button: button::State::new(),
+            cert_renewing: false,
+            cert_message: None,
+            deployed: false,
+            uninstall_button: button::State::new(),
+            uninstall_confirming: false,
+            uninstalling: false,
+            uninstall_report: None,
 
             pcip_modified: false,
             server_certs_install: false,
             rootca_to_sysstore: false,
             send_rootca_to_phone: false,
             ipaddr: String::new(),
+            port: DEFAULT_SYNC_PORT,
+            port_input: DEFAULT_SYNC_PORT.to_string(),
+            port_state: text_input::State::new(),
+            port_button: button::State::new(),
+            port_error: None,
+            lan_addrs: Vec::new(),
+            qr_codes: Vec::new(),
+            rootca_share_error: None,
             state_sync: text_input::State::new(),
             state_media: text_input::State::new(),
-            ready_for_pcmod: false,
-            ready_for_certsin: false,
-            ready_for_sysstore: false,
             ready_for_lookup: false,
 
             shortcut_sent: false,
             search_enable: false,
-            ready_for_shortcut: false,
-            ready_for_search: false,
+            running: None,
+
+            server_status: supervisor().status(),
+            server_start_button: button::State::new(),
+            server_stop_button: button::State::new(),
+            server_restart_button: button::State::new(),
+            diag_user: String::new(),
+            diag_pass: String::new(),
+            diag_user_state: text_input::State::new(),
+            diag_pass_state: text_input::State::new(),
+            diagnose_button: button::State::new(),
+            diagnosing: false,
+            diag_results: Vec::new(),
+            diag_error: None,
+            backups: Vec::new(),
+            backup_button: button::State::new(),
+            backing_up: false,
+            restore_confirming: None,
+            restoring: false,
+            backup_message: None,
+            config_backups: Vec::new(),
+            config_restore_confirming: None,
+            config_restoring: false,
+            config_message: None,
+            watch_addr: false,
+            watch_events: Vec::new(),
+            bundle_account: None,
+            bundle_with_account: false,
+            bundle_zip: false,
+            bundle_button: button::State::new(),
+            exporting: false,
+            bundle_message: None,
+            cjk_font: lang().needs_cjk(),
+            lang_pending: None,
+            plan_pending: None,
+            plan: None,
+            plan_message: None,
+            plan_scroll: scrollable::State::new(),
+            apply_button: button::State::new(),
+            plan_export_button: button::State::new(),
+            step_log: Vec::new(),
+            step_errors: Vec::new(),
+            log_expanded: false,
+            log_message: None,
+            log_scroll: scrollable::State::new(),
+            log_toggle_button: button::State::new(),
+            log_save_button: button::State::new(),
         }
     }
 }
 impl State {
-    fn advance(&mut self) {
-        if self.can_continue() {
-            self.current += 1;
-        }
-    }
-
-    fn go_back(&mut self) {
-        if self.has_previous() {
-            self.current -= 1;
+    /// what Page::is_complete looks at
+    fn progress(&self) -> Progress<'_> {
+        Progress {
+            anki_supported: self.verok,
+            phone_trusts_ca: self.send_rootca_to_phone,
+            done_before: &self.done_before,
         }
     }
-
-    fn has_previous(&self) -> bool {
-        self.current > 0
-    }
-    /// add step constrained conditions
-    fn can_continue(&self) -> bool {
-        self.current + 1 < 4 && true
+    /// where next leads,None if there is no page after this or it can't be entered yet
+    fn next_page(&self) -> Option<Page> {
+        self.page
+            .next(|s| self.step_done(s))
+            .filter(|p| p.can_enter(&self.progress()))
     }
-}
-#[derive(Debug)]
-struct Steps {
-    steps: Vec<Step>,
-    current: usize,
-}
-impl Default for Steps {
-    fn default() -> Self {
-        Steps {
-            steps: vec![
-                Step::Welcome {
-                    username: String::new(),
-                    password: String::new(),
-                    state_pass: text_input::State::new(),
-                    state_user: text_input::State::new(),
-                    selection: None,
-                    list_radio: false,
-                },
-                Step::SetIP,
-                Step::Shortcut,
-                Step::User,
-            ],
-            current: 0,
-        }
-    }
-}
-impl Steps {
-    // fn new() -> Steps {
-    //     Steps {
-    //         steps: vec![Step::Welcome, Step::SetIP, Step::Shortcut, Step::User],
-    //         current: 0,
-    //     }
-    // }
-    fn title(&self) -> &str {
-        self.steps[self.current].title()
-    }
-    // fn subscription(&self) {
-    //     self.steps[self.current].subscription();
-    // }
-    // fn update(&mut self, msg: Message) {
-    //     self.steps[self.current].update(msg);
-    // }
-
-    fn advance(&mut self) {
-        if self.can_continue() {
-            self.current += 1;
+    /// switch to page and start what it does on entry
+    fn enter(&mut self, page: Page) -> Command<Message> {
+        self.page = page;
+        self.list_radio_enable = false;
+        match page {
+            Page::SetIp => {
+                // the address may have changed,the steps follow the lookup
+                self.ready_for_lookup = true;
+                Command::none()
+            }
+            Page::Shortcut => {
+                self.redo(page);
+                self.plan_page()
+            }
+            Page::Server => Command::batch([
+                Command::perform(
+                    list_backups().map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                    Message::BackupsListed,
+                ),
+                Command::perform(
+                    list_config_backups(self.platform)
+                        .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                    Message::ConfigBackupsListed,
+                ),
+            ]),
+            Page::Welcome | Page::Users => Command::none(),
         }
     }
-
-    fn go_back(&mut self) {
-        if self.has_previous() {
-            self.current -= 1;
+    /// forget that page's steps were done,they run again once its plan is applied
+    fn redo(&mut self, page: Page) {
+        for step in page.steps() {
+            *self.step_done_mut(*step) = false;
         }
+        self.done_before.retain(|s| !page.steps().contains(s));
     }
-
-    fn has_previous(&self) -> bool {
-        self.current > 0
-    }
-
-    fn can_continue(&self) -> bool {
-        self.current + 1 < self.steps.len() && self.steps[self.current].can_continue()
-    }
-    fn view(&mut self, deploy_count: u8, verok: bool) -> Element<Message> {
-        self.steps[self.current].view(deploy_count, verok)
-    }
-}
-#[derive(Debug, Clone)]
-enum Step {
-    Welcome {
-        username: String,
-        password: String,
-        state_user: text_input::State,
-        state_pass: text_input::State,
-        selection: Option<UserOperation>,
-        list_radio: bool,
-    },
-    SetIP,
-    Shortcut,
-    User,
-}
-impl Default for Step {
-    fn default() -> Self {
-        Step::Welcome {
-            username: String::new(),
-            password: String::new(),
-            state_pass: text_input::State::new(),
-            state_user: text_input::State::new(),
-            selection: None,
-            list_radio: false,
+    fn step_done(&self, step: StepKind) -> bool {
+        match step {
+            StepKind::PcipModified => self.pcip_modified,
+            StepKind::CaImported => self.rootca_to_sysstore,
+            StepKind::CertsIssued => self.server_certs_install,
+            StepKind::ShortcutSent => self.shortcut_sent,
+            StepKind::StartMenuAdded => self.search_enable,
         }
     }
-}
-impl<'a> Step {
-    fn title(&self) -> &str {
-        match self {
-            Step::Welcome { .. } => "欢迎",
-            Self::SetIP => "设置IP",
-            Self::Shortcut => "快捷方式",
-            Self::User => "账户管理",
+    fn step_done_mut(&mut self, step: StepKind) -> &mut bool {
+        match step {
+            StepKind::PcipModified => &mut self.pcip_modified,
+            StepKind::CaImported => &mut self.rootca_to_sysstore,
+            StepKind::CertsIssued => &mut self.server_certs_install,
+            StepKind::ShortcutSent => &mut self.shortcut_sent,
+            StepKind::StartMenuAdded => &mut self.search_enable,
         }
     }
-
-    fn has_previous(&self, current: u8) -> bool {
-        current > 0
-    }
-
-    /// user management widgets for Welcome and user manage pages
-    fn radio(
-        selection: Option<UserOperation>,
-        username: &str,
-        password: &str,
-        state_user: &'a mut text_input::State,
-        state_pass: &'a mut text_input::State,
-    ) -> Column<'a, Message> {
-        // radio section
-        let radio_selections = Column::new()
-            .padding(20)
-            .spacing(10)
-            .push(Text::new("用户管理").size(24))
-            .push(UserOperation::all().iter().cloned().fold(
-                Row::new().padding(10).spacing(20),
-                |choices, language| {
-                    choices.push(Radio::new(
-                        language,
-                        language,
-                        selection,
-                        Message::UserOperationSeleted,
-                    ))
-                },
-            ));
-        // submit button for add/del/pass
-        // textinput/output section
-        let textio_section = match selection {
-            None => Column::new().push(Text::new("没有进行任何账号相关操作")),
-            Some(UserOperation::Add) => {
-                // two text input bars which denote username and password respectively
-                let user_input = TextInput::new(
-                    state_user,
-                    "Type something to continue...",
-                    username,
-                    Message::UserInputChanged,
-                )
-                .padding(10)
-                .size(30);
-                let pass_input = TextInput::new(
-                    state_pass,
-                    "Type something to continue...",
-                    password,
-                    Message::PassInputChanged,
-                )
-                .padding(10)
-                .size(30);
-                Column::new().push(user_input).push(pass_input)
-            }
-            Some(UserOperation::List) => {
-                // one text input which has read-only permission and place usernames
-
-                Column::new().push(Text::new(format!("users: {}", username)))
-                // Column::new().push(Text::new(format!("users: {}", userlist.users)))
-            }
-            Some(UserOperation::Delete) => {
-                // one text input which has write permission and for users to be deleted
-                let user_input = TextInput::new(
-                    state_user,
-                    "Type something to continue...",
-                    username,
-                    Message::UserInputChanged,
-                )
-                .padding(10)
-                .size(30);
-                Column::new().push(user_input)
-            }
-            Some(UserOperation::Pass) => {
-                // two text input bars which denote username and new password respectively
-                let user_input = TextInput::new(
-                    state_user,
-                    "Type something to continue...",
-                    username,
-                    Message::UserInputChanged,
-                )
-                .padding(10)
-                .size(30);
-                let newpass_input = TextInput::new(
-                    state_pass,
-                    "Type something to continue...",
-                    password,
-                    Message::PassInputChanged,
-                )
-                .padding(10)
-                .size(30);
-                Column::new().push(user_input).push(newpass_input)
-            }
-        };
-
-        // put radio and text sections in a new container
-        radio_selections.push(textio_section)
+    /// the lookup or a set ip step is still running on the old address and port
+    fn set_ip_busy(&self) -> bool {
+        self.ready_for_lookup || self.running.map_or(false, |s| Page::of(s) == Page::SetIp)
     }
-    fn container(title: &str) -> Column<'a, Message> {
-        Column::new().spacing(20).push(Text::new(title).size(50))
+    /// mark step done and run the one after it on its page
+    fn finish_step(&mut self, step: StepKind) {
+        *self.step_done_mut(step) = true;
+        self.step_errors.retain(|(s, _)| *s != step);
+        let steps = Page::of(step).steps();
+        self.running = steps.iter().skip_while(|s| **s != step).nth(1).copied();
     }
-    fn container_without_title() -> Column<'a, Message> {
-        Column::new().spacing(20)
+    /// stop the chain of steps at step,its error shows in the log panel
+    fn fail_step(&mut self, step: StepKind, error: String) {
+        self.running = None;
+        self.step_errors.retain(|(s, _)| *s != step);
+        self.step_errors.push((step, error));
+        self.log_expanded = true;
     }
-    /// display user manage controls if deploy_count >=1
-    /// render text with red if verok is false,else green
-    fn welcome(
-        deploy_count: u8,
-        verok: bool,
-        selection: Option<UserOperation>,
-        username: &str,
-        password: &str,
-        state_user: &'a mut text_input::State,
-        state_pass: &'a mut text_input::State,
-    ) -> Column<'a, Message> {
-        if verok {
-            if deploy_count >= 1 {
-                Self::container_without_title()
-                    .push(Text::new("PC Anki 版本符合要求！"))
-                    .push(Self::radio(
-                        selection, username, password, state_user, state_pass,
-                    ))
-            } else {
-                Self::container_without_title()
-                    .push(Text::new("PC Anki 版本符合要求！").color(Color::from_rgb8(0, 255, 0)))
+    /// write every line logged this session to deploy-log.txt
+    fn save_log(&self) -> Command<Message> {
+        let (platform, lines) = (self.platform, self.step_log.clone());
+        Command::perform(
+            async move {
+                let path = step_log_path(platform)?;
+                step_log::save(&lines, &path).await.map(|_| path)
             }
-        } else {
-            Self::container_without_title().push(
-                Text::new("PC Anki 版本不符合要求！请安装符合要求的Anki再打开本程序")
-                    .color(Color::from_rgb8(255, 0, 0)),
-            )
-        }
-    }
-    fn test() -> Column<'a, Message> {
-        Self::container("Welcome!").push(Text::new(
-            "This is a simple tour meant to showcase a bunch of widgets \
-                 that can be easily implemented on top of Iced.",
-        ))
+            .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+            Message::LogSaved,
+        )
     }
-    fn view(&mut self, deploy_count: u8, verok: bool) -> Element<Message> {
-        match self {
-            Step::Welcome {
-                username,
-                password,
-                state_user,
-                state_pass,
-                selection,
-                list_radio,
-            } => Self::welcome(
-                deploy_count,
-                verok,
-                *selection,
-                username,
-                password,
-                state_user,
-                state_pass,
-            ),
-            Self::SetIP => Self::test(),
-            Self::Shortcut => Self::test(),
-            Self::User => Self::test(),
-        }
-        .into()
+    /// work out what the steps of the current page change,they run once it's applied
+    fn plan_page(&mut self) -> Command<Message> {
+        self.plan_pending = Some(self.page);
+        self.plan = None;
+        self.plan_message = None;
+        let steps = self.page.steps().to_vec();
+        let (platform, ipaddr) = (self.platform, self.ipaddr.clone());
+        Command::perform(
+            async move { deploy_plan(platform, &steps, &ipaddr).await }
+                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+            Message::Planned,
+        )
     }
-
-    fn can_continue(&self) -> bool {
-        match self {
-            Self::Welcome { .. } => true,
-            Self::SetIP => true,
-            Self::Shortcut => true,
-            Self::User => false,
-        }
+    /// redraw QR codes after ipaddr or port changed
+    fn refresh_qr(&mut self) {
+        let addr = SyncAddr::new(Scheme::Https, &self.ipaddr, self.port);
+        let mut urls: Vec<(String, String)> = phone_urls(&addr)
+            .iter()
+            .map(|(label, url)| (label.to_string(), url.clone()))
+            .collect();
+        urls.push((tr("qr.rootca").into(), rootca_url(&self.ipaddr)));
+        self.qr_codes = urls
+            .into_iter()
+            .filter_map(|(label, url)| Some((label, qr_code::State::new(url).ok()?)))
+            .collect();
     }
 }
 #[derive(Debug, Clone)]
@@ -396,6 +421,10 @@ pub enum Message {
     NextPressed,
     Loaded(Result<LoadingConf, LoadError>),
     ExternalCMD(Result<(), CMDError>),
+    RenewPressed,
+    CertRenewed(Result<CertInfo, CMDError>),
+    UninstallPressed,
+    Uninstalled(Result<RollbackReport, CMDError>),
 
     UserOperationSeleted(UserOperation),
     UserInputChanged(String),
@@ -407,18 +436,61 @@ pub enum Message {
     SyncAddr(String),
     TogglerChanged(bool),
     IPAddrChanged(Event),
-    PCIPChanged(Event),
-    CAImported(Event),
-    ServCertsInstalled(Event),
-
-    MSLNKSent(Event),
-    SearchEnabled(Event),
+    /// index into lan_addrs
+    LanAddrSelected(usize),
+    LanChoiceSaved(Result<(), CMDError>),
+    PortInputChanged(String),
+    PortApplyPressed,
+    PortApplied(Result<u16, CMDError>),
+    /// rootCA download server stopped with an error
+    RootCaShared(Event),
+    StepProgressed(StepEvent),
+    LogToggled,
+    LogSavePressed,
+    LogSaved(Result<std::path::PathBuf, CMDError>),
+
+    ServerStartPressed,
+    ServerStopPressed,
+    ServerRestartPressed,
+    ServerStatusChanged(ServerStatus),
+    DiagUserChanged(String),
+    DiagPassChanged(String),
+    DiagnosePressed,
+    Diagnosed(Result<Vec<CheckResult>, CMDError>),
+    BackupsListed(Result<Vec<Archive>, CMDError>),
+    BackupPressed,
+    BackedUp(Result<Archive, CMDError>),
+    /// index into backups,pressed twice to confirm
+    RestorePressed(usize),
+    Restored(Result<std::path::PathBuf, CMDError>),
+    ConfigBackupsListed(Result<Vec<FileBackup>, CMDError>),
+    /// index into config_backups,pressed twice to confirm
+    ConfigRestorePressed(usize),
+    ConfigRestored(Result<std::path::PathBuf, CMDError>),
+    WatchToggled(bool),
+    AddrWatched(Vec<WatchEvent>),
+    BundleAccountToggled(bool),
+    BundleZipToggled(bool),
+    ExportPressed,
+    Exported(Result<std::path::PathBuf, CMDError>),
+    LangChosen(Lang),
+    LangSaved,
+    Planned(Result<Plan, CMDError>),
+    ApplyPressed,
+    PlanExportPressed,
+    PlanExported(Result<std::path::PathBuf, CMDError>),
 }
 #[derive(Debug, Clone)]
 pub enum Event {
     Start,
     Received(Option<String>),
 }
+/// what a running step reports
+#[derive(Debug, Clone)]
+pub enum StepEvent {
+    Logged(LogLine),
+    Finished(StepKind, Result<(), CMDError>),
+}
 fn button<'a, Message: Clone>(state: &'a mut button::State, label: &str) -> Button<'a, Message> {
     Button::new(
         state,
@@ -432,19 +504,19 @@ fn loading_message<'a>() -> Element<'a, Message> {
     let controls = Column::new();
     let content = if root_dir().exists() {
         controls.push(
-            Text::new("Loading...")
+            Text::new(tr("common.loading"))
                 .horizontal_alignment(alignment::Horizontal::Center)
                 .size(50),
         )
     } else {
         controls
             .push(
-                Text::new("Loading...")
+                Text::new(tr("common.loading"))
                     .horizontal_alignment(alignment::Horizontal::Center)
                     .size(50),
             )
             .push(
-                Text::new("未找到对应的文件夹")
+                Text::new(tr("common.root_missing"))
                     .color(Color::from_rgb8(255, 0, 0))
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
@@ -457,33 +529,20 @@ fn loading_message<'a>() -> Element<'a, Message> {
         .into()
 }
 impl<'a> App {
-    fn title(current: u8) -> &'static str {
-        match current {
-            0 => "欢迎",
-            1 => "设置IP",
-            2 => "快捷方式",
-            3 => "账户管理",
-            _ => "",
-        }
-    }
-    fn has_previous(current: u8) -> bool {
-        current > 0
-    }
-    /// add step constrained conditions
-    fn can_continue(current: u8, send_rootca_to_phone: bool) -> bool {
-        // set ip page
-        let can_continue = {
-            if send_rootca_to_phone {
-                true
-            } else {
-                false
-            }
-        };
-        if current == 1 {
-            return current + 1 < 4 && can_continue;
-        }
-
-        current + 1 < 4
+    /// every page's title,the current one larger and those whose steps are done green
+    fn progress(current: Page, done: &[Page]) -> Row<'a, Message> {
+        Page::all()
+            .into_iter()
+            .fold(Row::new().spacing(20).padding(10), |row, page| {
+                let text = Text::new(format!("{}. {}", page.number() + 1, page.title()));
+                row.push(if page == current {
+                    text.size(24)
+                } else if done.contains(&page) {
+                    text.size(16).color(Color::from_rgb8(0, 255, 0))
+                } else {
+                    text.size(16).color(Color::from_rgb8(128, 128, 128))
+                })
+            })
     }
     fn user_manage(
         selection: Option<UserOperation>,
@@ -507,21 +566,525 @@ impl<'a> App {
             ))
     }
 
+    /// ankisyncd state,start/stop/restart buttons and its latest output
+    fn server(
+        status: &ServerStatus,
+        start_btn_state: &'a mut button::State,
+        stop_btn_state: &'a mut button::State,
+        restart_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let (state_text, state_clr) = match &status.state {
+            ServerState::Stopped => (
+                tr("server.stopped").to_owned(),
+                Color::from_rgb8(128, 128, 128),
+            ),
+            ServerState::Running { pid } => {
+                (trf("server.running", &[pid]), Color::from_rgb8(0, 255, 0))
+            }
+            ServerState::Crashed {
+                code,
+                restarts,
+                retry_in,
+            } => (
+                trf(
+                    "server.crashed",
+                    &[
+                        &code.map_or(tr("common.none").to_owned(), |c| c.to_string()),
+                        &retry_in.as_secs(),
+                        restarts,
+                    ],
+                ),
+                Color::from_rgb8(255, 0, 0),
+            ),
+            ServerState::Failed(e) => (
+                trf("server.start_failed", &[e]),
+                Color::from_rgb8(255, 0, 0),
+            ),
+        };
+        let running = matches!(status.state, ServerState::Running { .. });
+        let stopped = matches!(status.state, ServerState::Stopped | ServerState::Failed(_));
+        let mut start = button(start_btn_state, tr("server.start"));
+        let mut stop = button(stop_btn_state, tr("server.stop"));
+        let mut restart = button(restart_btn_state, tr("server.restart"));
+        if stopped {
+            start = start
+                .on_press(Message::ServerStartPressed)
+                .style(style::Button::Primary);
+        } else {
+            stop = stop
+                .on_press(Message::ServerStopPressed)
+                .style(style::Button::Secondary);
+        }
+        if running {
+            restart = restart
+                .on_press(Message::ServerRestartPressed)
+                .style(style::Button::Secondary);
+        }
+        let controls = Row::new().spacing(20).push(start).push(stop).push(restart);
+
+        // the ring buffer keeps LOG_CAPACITY lines,the page only has room for a few
+        let tail = status
+            .log
+            .len()
+            .saturating_sub(SERVER_LOG_LINES.min(LOG_CAPACITY));
+        let log = status.log[tail..].iter().fold(
+            Column::new().spacing(2).width(Length::Fill),
+            |log, line| {
+                let text = Text::new(line.text.as_str()).size(14);
+                log.push(match line.stream {
+                    Stream::Stdout => text,
+                    Stream::Stderr => text.color(Color::from_rgb8(255, 0, 0)),
+                })
+            },
+        );
+        Self::container_without_title()
+            .align_items(alignment::Alignment::Center)
+            .push(
+                Row::new()
+                    .push(Text::new(trf("common.label", &[&"ankisyncd"])))
+                    .push(Text::new(state_text).color(state_clr)),
+            )
+            .push(controls)
+            .push(log)
+    }
+
+    /// port input,applied only when nothing else listens on it
+    fn port_section(
+        port_input: &str,
+        busy: bool,
+        port_error: Option<&str>,
+        port_state: &'a mut text_input::State,
+        port_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let btn = if busy {
+            button(port_btn_state, tr("server.apply"))
+        } else {
+            button(port_btn_state, tr("server.apply"))
+                .on_press(Message::PortApplyPressed)
+                .style(style::Button::Secondary)
+        };
+        let row = Row::new()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(Text::new(tr("server.port")))
+            .push(
+                TextInput::new(
+                    port_state,
+                    tr("server.port_placeholder"),
+                    port_input,
+                    Message::PortInputChanged,
+                )
+                .padding(10)
+                .width(Length::Units(120)),
+            )
+            .push(btn);
+        let section = Self::container_without_title().spacing(10).push(row);
+        match port_error {
+            Some(e) => section.push(Text::new(e).color(Color::from_rgb8(255, 0, 0))),
+            None => section,
+        }
+    }
+    /// test account inputs,diagnose button and one row per check
+    fn diagnostics(
+        username: &str,
+        password: &str,
+        diagnosing: bool,
+        results: &[CheckResult],
+        error: Option<&str>,
+        state_user: &'a mut text_input::State,
+        state_pass: &'a mut text_input::State,
+        diagnose_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let btn = if diagnosing {
+            button(diagnose_btn_state, tr("diag.running"))
+        } else {
+            button(diagnose_btn_state, tr("diag.run"))
+                .on_press(Message::DiagnosePressed)
+                .style(style::Button::Primary)
+        };
+        let inputs = Row::new()
+            .spacing(10)
+            .push(
+                TextInput::new(
+                    state_user,
+                    tr("diag.test_user"),
+                    username,
+                    Message::DiagUserChanged,
+                )
+                .padding(10),
+            )
+            .push(
+                TextInput::new(
+                    state_pass,
+                    tr("common.password"),
+                    password,
+                    Message::DiagPassChanged,
+                )
+                .password()
+                .padding(10),
+            )
+            .push(btn);
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(inputs);
+        if let Some(e) = error {
+            section = section.push(Text::new(e).color(Color::from_rgb8(255, 0, 0)));
+        }
+        results.iter().fold(section, |section, result| {
+            let (status, clr) = match &result.outcome {
+                Outcome::Ok => (tr("common.ok").to_owned(), Color::from_rgb8(0, 255, 0)),
+                Outcome::Failed(problem) => (problem_tips(problem), Color::from_rgb8(255, 0, 0)),
+                Outcome::Skipped => (
+                    tr("diag.skipped").to_owned(),
+                    Color::from_rgb8(128, 128, 128),
+                ),
+            };
+            section.push(
+                Row::new()
+                    .push(Text::new(trf(
+                        "common.label",
+                        &[&check_label(result.check)],
+                    )))
+                    .push(Text::new(status).color(clr)),
+            )
+        })
+    }
+
+    /// backup now button and the newest archives,restored after a second press
+    fn backups(
+        backups: &'a mut [(Archive, button::State)],
+        backing_up: bool,
+        restoring: bool,
+        confirming: Option<usize>,
+        message: Option<&(String, bool)>,
+        backup_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let busy = backing_up || restoring;
+        let mut backup_btn = button(
+            backup_btn_state,
+            if backing_up {
+                tr("backup.running")
+            } else {
+                tr("backup.now")
+            },
+        );
+        if !busy {
+            backup_btn = backup_btn
+                .on_press(Message::BackupPressed)
+                .style(style::Button::Primary);
+        }
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(backup_btn);
+        if let Some((text, failed)) = message {
+            let clr = if *failed {
+                Color::from_rgb8(255, 0, 0)
+            } else {
+                Color::from_rgb8(0, 255, 0)
+            };
+            section = section.push(Text::new(text.as_str()).color(clr));
+        }
+        for (i, (archive, btn_state)) in backups.iter_mut().take(BACKUP_ROWS).enumerate() {
+            let name = archive
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let label = if restoring && confirming == Some(i) {
+                tr("backup.restoring")
+            } else if confirming == Some(i) {
+                tr("backup.confirm_restore")
+            } else {
+                tr("backup.restore")
+            };
+            let mut btn = button(btn_state, label);
+            if !busy {
+                btn = btn
+                    .on_press(Message::RestorePressed(i))
+                    .style(style::Button::Secondary);
+            }
+            section = section.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(alignment::Alignment::Center)
+                    .push(
+                        Text::new(trf("backup.archive", &[&name, &(archive.size / 1024)])).size(14),
+                    )
+                    .push(btn),
+            );
+        }
+        section
+    }
+
+    /// earlier versions of the config files,restored after a second press
+    fn config_backups(
+        backups: &'a mut [(FileBackup, button::State)],
+        restoring: bool,
+        confirming: Option<usize>,
+        message: Option<&(String, bool)>,
+    ) -> Column<'a, Message> {
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(Text::new(tr("config_backup.title")).size(20));
+        if let Some((text, failed)) = message {
+            let clr = if *failed {
+                Color::from_rgb8(255, 0, 0)
+            } else {
+                Color::from_rgb8(0, 255, 0)
+            };
+            section = section.push(Text::new(text.as_str()).color(clr));
+        }
+        if backups.is_empty() {
+            return section.push(Text::new(tr("config_backup.none")).size(14));
+        }
+        for (i, (backup, btn_state)) in backups.iter_mut().take(BACKUP_ROWS).enumerate() {
+            let name = backup
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let label = if restoring && confirming == Some(i) {
+                tr("backup.restoring")
+            } else if confirming == Some(i) {
+                tr("backup.confirm_restore")
+            } else {
+                tr("backup.restore")
+            };
+            let mut btn = button(btn_state, label);
+            if !restoring {
+                btn = btn
+                    .on_press(Message::ConfigRestorePressed(i))
+                    .style(style::Button::Secondary);
+            }
+            section = section.push(
+                Row::new()
+                    .spacing(20)
+                    .align_items(alignment::Alignment::Center)
+                    .push(Text::new(trf("config_backup.entry", &[&name, &backup.size])).size(14))
+                    .push(btn),
+            );
+        }
+        section
+    }
+
+    /// address watch toggle and its latest events
+    fn addr_watch_section(watching: bool, events: &[WatchEvent]) -> Column<'a, Message> {
+        let toggler = Container::new(Toggler::new(
+            watching,
+            String::from(tr("watch.toggle")),
+            Message::WatchToggled,
+        ))
+        .padding([0, 40]);
+        let tail = events.len().saturating_sub(WATCH_EVENT_LINES);
+        events[tail..].iter().fold(
+            Self::container_without_title()
+                .spacing(5)
+                .align_items(alignment::Alignment::Center)
+                .push(toggler),
+            |section, event| {
+                let (text, clr) = watch_event_text(event);
+                section.push(Text::new(text).size(14).color(clr))
+            },
+        )
+    }
+
+    /// changes the page's steps will make,applied or exported by the user
+    fn plan_section(
+        plan: Option<&Plan>,
+        message: Option<&(String, bool)>,
+        scroll: &'a mut scrollable::State,
+        apply_btn_state: &'a mut button::State,
+        export_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(Text::new(tr("plan.title")));
+        if let Some(plan) = plan {
+            let mut list = Scrollable::new(scroll)
+                .spacing(5)
+                .max_height(PLAN_HEIGHT)
+                .width(Length::Fill);
+            for step in &plan.steps {
+                list = list.push(Text::new(step_label(step.step)).size(16));
+                if step.operations.is_empty() {
+                    list = list.push(Text::new(tr("plan.nothing")).size(14));
+                }
+                for op in &step.operations {
+                    list = list.push(Text::new(operation_text(op)).size(14));
+                    for line in op.diff().lines() {
+                        let clr = match line.chars().next() {
+                            Some('+') if !line.starts_with("+++") => Color::from_rgb8(0, 160, 0),
+                            Some('-') if !line.starts_with("---") => Color::from_rgb8(255, 0, 0),
+                            _ => Color::from_rgb8(128, 128, 128),
+                        };
+                        list = list.push(Text::new(line).size(12).color(clr));
+                    }
+                }
+            }
+            let apply = button(apply_btn_state, tr("plan.apply"))
+                .on_press(Message::ApplyPressed)
+                .style(style::Button::Primary);
+            let export = button(export_btn_state, tr("plan.export"))
+                .on_press(Message::PlanExportPressed)
+                .style(style::Button::Secondary);
+            section = section
+                .push(list)
+                .push(Row::new().spacing(20).push(apply).push(export));
+        } else if message.is_none() {
+            section = section.push(Text::new(tr("plan.planning")).size(14));
+        }
+        if let Some((text, failed)) = message {
+            let clr = if *failed {
+                Color::from_rgb8(255, 0, 0)
+            } else {
+                Color::from_rgb8(0, 255, 0)
+            };
+            section = section.push(Text::new(text.as_str()).color(clr));
+        }
+        section
+    }
+    /// what page's steps logged,failed steps with their error
+    ///
+    /// lines stay folded until expanded or a step fails
+    fn step_log_section(
+        page: Page,
+        lines: &[LogLine],
+        errors: &[(StepKind, String)],
+        expanded: bool,
+        message: Option<&(String, bool)>,
+        scroll: &'a mut scrollable::State,
+        toggle_btn_state: &'a mut button::State,
+        save_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let lines: Vec<&LogLine> = lines.iter().filter(|l| Page::of(l.step) == page).collect();
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center);
+        for (step, error) in errors.iter().filter(|(s, _)| Page::of(*s) == page) {
+            section = section.push(
+                Text::new(trf("log.step_failed", &[&step_label(*step), error]))
+                    .color(Color::from_rgb8(255, 0, 0)),
+            );
+        }
+        if lines.is_empty() {
+            return section;
+        }
+        let toggle = if expanded {
+            tr("log.hide").to_owned()
+        } else {
+            trf("log.show", &[&lines.len()])
+        };
+        let toggle = button(toggle_btn_state, &toggle)
+            .on_press(Message::LogToggled)
+            .style(style::Button::Secondary);
+        let save = button(save_btn_state, tr("log.save"))
+            .on_press(Message::LogSavePressed)
+            .style(style::Button::Secondary);
+        section = section.push(Row::new().spacing(20).push(toggle).push(save));
+        if expanded {
+            let list = lines.iter().fold(
+                Scrollable::new(scroll)
+                    .spacing(2)
+                    .max_height(STEP_LOG_HEIGHT)
+                    .width(Length::Fill),
+                |list, line| {
+                    let text = Text::new(line.to_string()).size(12);
+                    list.push(match line.source {
+                        Source::Command => text.color(Color::from_rgb8(0, 128, 255)),
+                        Source::Stdout => text,
+                        Source::Stderr => text.color(Color::from_rgb8(255, 0, 0)),
+                        Source::Status => text.color(Color::from_rgb8(128, 128, 128)),
+                    })
+                },
+            );
+            section = section.push(list);
+        }
+        if let Some((text, failed)) = message {
+            let clr = if *failed {
+                Color::from_rgb8(255, 0, 0)
+            } else {
+                Color::from_rgb8(0, 255, 0)
+            };
+            section = section.push(Text::new(text.as_str()).color(clr));
+        }
+        section
+    }
+    /// export rootCA.crt,the sync urls and setup steps for phones and other PCs
+    fn bundle_section(
+        account: Option<&Credentials>,
+        with_account: bool,
+        zip: bool,
+        exporting: bool,
+        message: Option<&(String, bool)>,
+        btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let mut export_btn = button(
+            btn_state,
+            if exporting {
+                tr("bundle.exporting")
+            } else {
+                tr("bundle.export")
+            },
+        );
+        if !exporting {
+            export_btn = export_btn
+                .on_press(Message::ExportPressed)
+                .style(style::Button::Primary);
+        }
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(Text::new(tr("bundle.contents")));
+        if let Some(account) = account {
+            section = section.push(
+                Container::new(Toggler::new(
+                    with_account,
+                    trf("bundle.with_account", &[&account.username]),
+                    Message::BundleAccountToggled,
+                ))
+                .padding([0, 40]),
+            );
+        }
+        section = section
+            .push(
+                Container::new(Toggler::new(
+                    zip,
+                    String::from(tr("bundle.zip")),
+                    Message::BundleZipToggled,
+                ))
+                .padding([0, 40]),
+            )
+            .push(export_btn);
+        if let Some((text, failed)) = message {
+            let clr = if *failed {
+                Color::from_rgb8(255, 0, 0)
+            } else {
+                Color::from_rgb8(0, 255, 0)
+            };
+            section = section.push(Text::new(text.as_str()).color(clr));
+        }
+        section
+    }
+
     fn shortcut_search(shortcut_sent: bool, search_enable: bool) -> Column<'a, Message> {
         let (shortcut_status, shortcut_clr) = if shortcut_sent {
-            ("OK", Color::from_rgb8(0, 255, 0))
+            (tr("common.ok"), Color::from_rgb8(0, 255, 0))
         } else {
             ("...", Color::from_rgb8(255, 0, 0))
         };
         let (search_status, search_clr) = if search_enable {
-            ("OK", Color::from_rgb8(0, 255, 0))
+            (tr("common.ok"), Color::from_rgb8(0, 255, 0))
         } else {
             ("...", Color::from_rgb8(255, 0, 0))
         };
 
         let shortcut_section = Row::new()
             .push(
-                Text::new("发送anki_server到桌面：")
+                Text::new(tr("shortcut.desktop"))
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
             .push(
@@ -532,7 +1095,7 @@ impl<'a> App {
 
         let seach_section = Row::new()
             .push(
-                Text::new("复制快捷方式到Windows开始菜单：")
+                Text::new(tr("shortcut.start_menu"))
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
             .push(
@@ -546,7 +1109,7 @@ impl<'a> App {
             .push(shortcut_section)
             .push(seach_section)
             .push(
-                Text::new("这意味着可以从开始菜单搜索启动服务器软件")
+                Text::new(tr("shortcut.start_menu_tip"))
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
     }
@@ -556,20 +1119,21 @@ impl<'a> App {
         rootca_to_sysstore: bool,
         send_rootca_to_phone: bool,
         ipaddr: &str,
+        port: u16,
+        lan_addrs: &[LanAddr],
+        qr_codes: &'a [(String, qr_code::State)],
+        rootca_share_error: Option<&str>,
         state_sync: &'a mut text_input::State,
         state_media: &'a mut text_input::State,
     ) -> Column<'a, Message> {
         // state_check section text
         let (pcip_mod_status, pcip_mod_clr) = if pcip_modified {
-            ("OK", Color::from_rgb8(0, 255, 0))
+            (tr("common.ok"), Color::from_rgb8(0, 255, 0))
         } else {
             ("...", Color::from_rgb8(255, 0, 0))
         };
         let pcip_mod = Row::new()
-            .push(
-                Text::new("修改PC Anki 同步地址：")
-                    .horizontal_alignment(alignment::Horizontal::Center),
-            )
+            .push(Text::new(tr("set_ip.pcip")).horizontal_alignment(alignment::Horizontal::Center))
             .push(
                 Text::new(pcip_mod_status)
                     .color(pcip_mod_clr)
@@ -578,13 +1142,13 @@ impl<'a> App {
 
         // rootca_to_sysstore
         let (sysstore_status, sysstore_clr) = if let true = rootca_to_sysstore {
-            ("OK", Color::from_rgb8(0, 255, 0))
+            (tr("common.ok"), Color::from_rgb8(0, 255, 0))
         } else {
             ("...", Color::from_rgb8(255, 0, 0))
         };
         let sysstore = Row::new()
             .push(
-                Text::new("安装证书到系统证书区：")
+                Text::new(tr("set_ip.sysstore"))
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
             .push(
@@ -595,47 +1159,56 @@ impl<'a> App {
 
         // server_certs_install
         let (certs_install_status, certs_install_clr) = if let true = server_certs_install {
-            ("OK", Color::from_rgb8(0, 255, 0))
+            (tr("common.ok"), Color::from_rgb8(0, 255, 0))
         } else {
             ("...", Color::from_rgb8(255, 0, 0))
         };
         let certs_install = Row::new()
-            .push(
-                Text::new("签发服务器证书并安装：")
-                    .horizontal_alignment(alignment::Horizontal::Center),
-            )
+            .push(Text::new(tr("set_ip.certs")).horizontal_alignment(alignment::Horizontal::Center))
             .push(
                 Text::new(certs_install_status)
                     .color(certs_install_clr)
                     .horizontal_alignment(alignment::Horizontal::Center),
             );
 
+        // pick among this machine's addresses,phones must reach the chosen one
+        let selected = lan_addrs.iter().position(|a| a.ip.to_string() == ipaddr);
+        let lan_section = lan_addrs.iter().enumerate().fold(
+            Self::container_without_title()
+                .spacing(10)
+                .push(Text::new(tr("set_ip.choose_lan"))),
+            |choices, (i, addr)| {
+                choices.push(Radio::new(
+                    i,
+                    addr.to_string(),
+                    selected,
+                    Message::LanAddrSelected,
+                ))
+            },
+        );
+
         let state_check_section = Self::container_without_title()
             .push(pcip_mod)
             .push(sysstore)
             .push(certs_install);
 
         // sync and media sync address display section text_input
+        let addr = SyncAddr::new(Scheme::Https, ipaddr, port);
         let sync_addr = Row::new()
-            .push(Text::new("同步地址：").vertical_alignment(alignment::Vertical::Bottom))
+            .push(Text::new(tr("set_ip.sync_url")).vertical_alignment(alignment::Vertical::Bottom))
             .push(
-                TextInput::new(
-                    state_sync,
-                    "...",
-                    &format!("https://{}:27701", ipaddr),
-                    Message::SyncAddr,
-                )
-                .padding(10)
-                .size(20),
+                TextInput::new(state_sync, "...", &addr.base_url(), Message::SyncAddr)
+                    .padding(10)
+                    .size(20),
             )
             .align_items(alignment::Alignment::Fill);
         let media_addr = Row::new()
-            .push(Text::new("媒体文件同步地址：").vertical_alignment(alignment::Vertical::Bottom))
+            .push(Text::new(tr("set_ip.media_url")).vertical_alignment(alignment::Vertical::Bottom))
             .push(
                 TextInput::new(
                     state_media,
                     "...",
-                    &format!("https://{}:27701/msync", ipaddr),
+                    &format!("{}/msync", addr.base_url()),
                     Message::SyncAddr,
                 )
                 .padding(10)
@@ -646,11 +1219,36 @@ impl<'a> App {
             .push(sync_addr)
             .push(media_addr);
 
+        // scan with AnkiDroid instead of typing the addresses
+        let qr_row = qr_codes
+            .iter()
+            .fold(Row::new().spacing(20), |row, (label, code)| {
+                row.push(
+                    Column::new()
+                        .spacing(5)
+                        .align_items(alignment::Alignment::Center)
+                        .push(QRCode::new(code).cell_size(3))
+                        .push(Text::new(label.as_str()).size(16)),
+                )
+            });
+        let mut qr_section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(qr_row)
+            .push(Text::new(tr("set_ip.scan_rootca")).size(16));
+        if let Some(e) = rootca_share_error {
+            qr_section = qr_section.push(
+                Text::new(trf("set_ip.share_failed", &[&e]))
+                    .size(16)
+                    .color(Color::from_rgb8(255, 0, 0)),
+            );
+        }
+
         // user confirm checkbox to make next step can continue
         let next_confirm = Self::container_without_title().push(
             Container::new(Toggler::new(
                 send_rootca_to_phone,
-                String::from("将rootCA.crt发送到手机并安装，修改手机Anki同步地址，确认？"),
+                String::from(tr("set_ip.confirm_phone")),
                 Message::TogglerChanged,
             ))
             .padding([0, 40])
@@ -658,8 +1256,10 @@ impl<'a> App {
         );
         Self::container_without_title()
             .align_items(alignment::Alignment::Center)
+            .push(lan_section)
             .push(state_check_section)
             .push(addr_show_section)
+            .push(qr_section)
             .push(next_confirm)
     }
     /// user management widgets for Welcome and user manage pages
@@ -678,7 +1278,7 @@ impl<'a> App {
             .spacing(10)
             .align_items(alignment::Alignment::Center)
             .push(
-                Text::new("用户管理")
+                Text::new(tr("users.title"))
                     .size(24)
                     .horizontal_alignment(alignment::Horizontal::Center),
             )
@@ -696,14 +1296,24 @@ impl<'a> App {
 
         // submit button for add/del/pass
         // textinput/output section
-        let user_input = TextInput::new(state_user, "用户名", username, Message::UserInputChanged)
-            .padding(10)
-            .size(30);
-        let pass_input = TextInput::new(state_pass, "密码", password, Message::PassInputChanged)
-            .padding(10)
-            .size(30);
+        let user_input = TextInput::new(
+            state_user,
+            tr("common.username"),
+            username,
+            Message::UserInputChanged,
+        )
+        .padding(10)
+        .size(30);
+        let pass_input = TextInput::new(
+            state_pass,
+            tr("common.password"),
+            password,
+            Message::PassInputChanged,
+        )
+        .padding(10)
+        .size(30);
         let textio_section = match selection {
-            None => Column::new().push(Text::new("没有进行任何账号相关操作")),
+            None => Column::new().push(Text::new(tr("users.no_operation"))),
             Some(UserOperation::List) => {
                 // one text input which has read-only permission and place usernames
 
@@ -726,7 +1336,7 @@ impl<'a> App {
         let submit_btn_section = match selection {
             None | Some(UserOperation::List) => Row::new().push(Text::new("")),
             _ => {
-                let btn = Button::new(submit_btn_state, Text::new("提交"))
+                let btn = Button::new(submit_btn_state, Text::new(tr("users.submit")))
                     .on_press(Message::Submit(username.into(), password.into()))
                     .style(style::Button::Primary);
 
@@ -741,17 +1351,121 @@ impl<'a> App {
             .push(textio_section)
             .push(submit_btn_section)
     }
-    fn container(title: &str) -> Column<'a, Message> {
-        Column::new().spacing(20).push(Text::new(title).size(50))
+    /// side effect of the step the wizard is at,one at a time in order
+    fn step_subscription(state: &State) -> Subscription<Message> {
+        if state.list_radio_enable {
+            return list().map(Message::TextContentChanged);
+        }
+
+        // set ip page
+        if state.ready_for_lookup {
+            // look up ip address
+
+            return look_up().map(Message::IPAddrChanged);
+        }
+        // steps of the set ip and shortcut pages,each one starts the next
+        if let Some(step) = state.running {
+            return step_run(state.platform, step, &state.ipaddr).map(Message::StepProgressed);
+        }
+
+        Subscription::none()
     }
     fn container_without_title() -> Column<'a, Message> {
         Column::new().spacing(20)
     }
+    /// expiry date and addrs of server cert,warn and offer renewal
+    /// if ipaddr is missing or it expires soon
+    fn cert_status(
+        cert_info: Option<&CertInfo>,
+        ipaddr: &str,
+        cert_renewing: bool,
+        message: Option<&str>,
+        renew_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let info = match cert_info {
+            Some(info) => info,
+            None => return Self::container_without_title(),
+        };
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center)
+            .push(Text::new(trf(
+                "cert.expiry",
+                &[&info.expiry_date(), &info.days_left()],
+            )))
+            .push(Text::new(trf("cert.sans", &[&info.sans.join(" ")])));
+        if !ipaddr.is_empty() && !info.covers(ipaddr) {
+            section = section.push(
+                Text::new(trf("cert.ip_missing", &[&ipaddr])).color(Color::from_rgb8(255, 0, 0)),
+            );
+        } else if info.needs_renewal(ipaddr) {
+            section =
+                section.push(Text::new(tr("cert.expiring")).color(Color::from_rgb8(255, 0, 0)));
+        }
+        if let Some(msg) = message {
+            section = section.push(
+                Text::new(trf("cert.renew_failed", &[&msg])).color(Color::from_rgb8(255, 0, 0)),
+            );
+        }
+        if !info.needs_renewal(ipaddr) {
+            return section;
+        }
+        let btn = if cert_renewing {
+            button(renew_btn_state, tr("cert.renewing"))
+        } else {
+            button(renew_btn_state, tr("cert.renew"))
+                .on_press(Message::RenewPressed)
+                .style(style::Button::Primary)
+        };
+        section.push(btn)
+    }
+    /// uninstall button,pressed twice to confirm,and the outcome of the last uninstall
+    fn uninstall_section(
+        deployed: bool,
+        confirming: bool,
+        uninstalling: bool,
+        report: Option<&RollbackReport>,
+        uninstall_btn_state: &'a mut button::State,
+    ) -> Column<'a, Message> {
+        let mut section = Self::container_without_title()
+            .spacing(10)
+            .align_items(alignment::Alignment::Center);
+        if let Some(report) = report {
+            if report.is_clean() {
+                section = section
+                    .push(Text::new(tr("uninstall.clean")).color(Color::from_rgb8(0, 255, 0)));
+            } else {
+                section = section
+                    .push(Text::new(tr("uninstall.failed")).color(Color::from_rgb8(255, 0, 0)));
+                for (action, error) in report.failed.iter() {
+                    section = section.push(
+                        Text::new(trf("common.pair", &[action, error]))
+                            .color(Color::from_rgb8(255, 0, 0)),
+                    );
+                }
+            }
+        }
+        if !deployed {
+            return section;
+        }
+        let btn = if uninstalling {
+            button(uninstall_btn_state, tr("uninstall.running"))
+        } else if confirming {
+            button(uninstall_btn_state, tr("uninstall.confirm"))
+                .on_press(Message::UninstallPressed)
+                .style(style::Button::Primary)
+        } else {
+            button(uninstall_btn_state, tr("uninstall.run"))
+                .on_press(Message::UninstallPressed)
+                .style(style::Button::Secondary)
+        };
+        section.push(btn)
+    }
     /// display user manage controls if deploy_count >=1
-    /// render text with red if verok is false,else green
+    /// render text with red if PC Anki is missing or too old,else green
     fn welcome(
-        deploy_count: u8,
-        verok: bool,
+        deploy_count: u32,
+        anki_version: Option<AnkiVersion>,
         selection: Option<UserOperation>,
         username: &str,
         password: &str,
@@ -759,12 +1473,16 @@ impl<'a> App {
         state_pass: &'a mut text_input::State,
         submit_btn_state: &'a mut button::State,
         useracnt_optips: &str,
+        cert_section: Column<'a, Message>,
+        uninstall_section: Column<'a, Message>,
     ) -> Column<'a, Message> {
-        if verok {
+        if let Some(version) = anki_version.filter(AnkiVersion::is_supported) {
+            let ver_text = Self::anki_version_text(version);
             if deploy_count >= 1 {
                 Self::container_without_title()
                     .align_items(alignment::Alignment::Center)
-                    .push(Text::new("PC Anki 版本符合要求！").color(Color::from_rgb8(0, 255, 0)))
+                    .push(Text::new(ver_text).color(Color::from_rgb8(0, 255, 0)))
+                    .push(cert_section)
                     .push(Self::radio(
                         selection,
                         username,
@@ -774,32 +1492,72 @@ impl<'a> App {
                         submit_btn_state,
                         useracnt_optips,
                     ))
+                    .push(uninstall_section)
             } else {
                 Self::container_without_title()
                     .align_items(alignment::Alignment::Center)
-                    .push(Text::new("PC Anki 版本符合要求！").color(Color::from_rgb8(0, 255, 0)))
+                    .push(Text::new(ver_text).color(Color::from_rgb8(0, 255, 0)))
+                    .push(uninstall_section)
             }
         } else {
             Self::container_without_title()
                 .align_items(alignment::Alignment::Center)
                 .push(
-                    Text::new("PC Anki 版本不符合要求！请安装符合要求的Anki再打开本程序")
-                        .color(Color::from_rgb8(255, 0, 0)),
+                    Text::new(match anki_version {
+                        Some(v) => trf("welcome.anki_too_old", &[&v]),
+                        None => tr("welcome.anki_missing").into(),
+                    })
+                    .color(Color::from_rgb8(255, 0, 0)),
                 )
         }
     }
-    fn test() -> Column<'a, Message> {
-        Self::container("Welcome!").push(Text::new(
-            "This is a simple tour meant to showcase a bunch of widgets \
-                 that can be easily implemented on top of Iced.",
-        ))
+    /// language picker,Chinese needs a restart unless simhei is loaded
+    fn lang_section(pending: Option<Lang>, cjk_font: bool) -> Column<'a, Message> {
+        let choices = Lang::all().into_iter().fold(
+            Row::new()
+                .padding(10)
+                .spacing(20)
+                .align_items(alignment::Alignment::Center)
+                .push(Text::new(tr("lang.title"))),
+            |choices, choice| {
+                let mut radio = Radio::new(
+                    choice,
+                    choice.native_name(),
+                    Some(pending.unwrap_or_else(lang)),
+                    Message::LangChosen,
+                );
+                if choice.needs_cjk() && !cjk_font {
+                    radio = radio.font(CJK_FONT);
+                }
+                choices.push(radio)
+            },
+        );
+        let mut section = Self::container_without_title()
+            .align_items(alignment::Alignment::Center)
+            .push(choices);
+        if let Some(pending) = pending {
+            section = section.push(
+                Text::new(trf("lang.restart", &[&pending]))
+                    .font(CJK_FONT)
+                    .color(Color::from_rgb8(255, 0, 0)),
+            );
+        }
+        section
+    }
+    /// newer Anki takes the sync server in its preferences,older ones via the addon
+    fn anki_version_text(version: AnkiVersion) -> String {
+        if version.has_builtin_sync() {
+            trf("welcome.anki_builtin", &[&version])
+        } else {
+            trf("welcome.anki_addon", &[&version])
+        }
     }
 }
 impl Application for App {
     type Executor = executor::Default;
     type Message = Message;
     type Flags = ();
-    ///read file deploy_count.txt when app is initiated
+    ///read deployment manifest when app is initiated
     fn new(_flags: ()) -> (App, Command<Message>) {
         (
             App::Loading,
@@ -810,46 +1568,31 @@ impl Application for App {
     fn title(&self) -> String {
         let title = match self {
             App::Loading => "loading",
-            App::Loaded(state) => Self::title(state.current),
+            App::Loaded(state) => state.page.title(),
         };
         format!("{} - anki sync server deployer", title)
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        match self {
-            App::Loaded(state) => {
-                if state.list_radio_enable {
-                    return list().map(Message::TextContentChanged);
-                }
-
-                // set ip page
-                if state.ready_for_lookup {
-                    // look up ip address
-
-                    return look_up().map(Message::IPAddrChanged);
-                }
-                // set pc anki sync address
-                if state.ready_for_pcmod {
-                    return pcip_mod(&state.ipaddr).map(Message::PCIPChanged);
-                }
-                // install rootCA and export it to sys store
-                if state.ready_for_sysstore {
-                    return import_sysstore().map(Message::CAImported);
-                }
-                //  gen server certs and install(write their paths to Stetings.toml)
-                if state.ready_for_certsin {
-                    return srvcerts_install(&state.ipaddr).map(Message::ServCertsInstalled);
-                }
-
-                // step 3 send shortcui to desktop
-                if state.ready_for_shortcut {
-                    return shortcut().map(Message::MSLNKSent);
-                }
-                if state.ready_for_search {
-                    return seach().map(Message::SearchEnabled);
-                }
-
-                Subscription::none()
+        match self {
+            App::Loaded(state) => {
+                // phones download rootCA.crt while the set ip page is open
+                let share = if state.page == Page::SetIp && state.rootca_to_sysstore {
+                    share_rootca(&state.ipaddr).map(Message::RootCaShared)
+                } else {
+                    Subscription::none()
+                };
+                let server = if state.page == Page::Server {
+                    server_status().map(Message::ServerStatusChanged)
+                } else {
+                    Subscription::none()
+                };
+                let watch = if state.watch_addr {
+                    addr_changes(state.platform).map(Message::AddrWatched)
+                } else {
+                    Subscription::none()
+                };
+                Subscription::batch(vec![Self::step_subscription(state), share, server, watch])
             }
             _ => Subscription::none(),
         }
@@ -860,10 +1603,28 @@ impl Application for App {
                 match message {
                     Message::Loaded(Ok(state)) => {
                         *self = App::Loaded(State {
+                            platform: state.platform,
                             verok: state.verok,
+                            anki_version: state.anki_version,
                             deploy_count: state.deploy_count,
+                            cert_info: state.cert_info,
+                            ipaddr: state.ipaddr,
+                            port: state.port,
+                            port_input: state.port.to_string(),
+                            lan_addrs: state.lan_addrs,
+                            deployed: state.deployed,
+                            done_before: state.done_steps.clone(),
                             ..State::default()
                         });
+                        let resume = state.resume_page;
+                        if let App::Loaded(state) = self {
+                            for step in state.done_before.clone() {
+                                *state.step_done_mut(step) = true;
+                            }
+                            state.refresh_qr();
+                            // pick up where an interrupted deployment stopped
+                            return state.enter(resume);
+                        }
                     }
                     _ => {}
                 }
@@ -871,38 +1632,14 @@ impl Application for App {
             }
             App::Loaded(state) => {
                 match message {
-                    Message::BackPressed => {
-                        state.go_back();
-                        // step 2 set ip page
-                        if state.current == 1 {
-                            state.ready_for_lookup = true;
-                        } else if state.current == 2 {
-                            // step 3  send shortcut to desktop
-                            state.ready_for_shortcut = true;
-                            state.shortcut_sent = false;
-                            state.search_enable = false;
-                            state.list_radio_enable = false;
-                        }
-                        Command::none()
-                    }
-                    Message::NextPressed => {
-                        state.advance();
-                        // step 2 set ip page
-                        if state.current == 1 {
-                            state.ready_for_lookup = true;
-                            state.list_radio_enable = false;
-                        } else if state.current == 2 {
-                            // step 3  send shortcut to desktop
-                            state.ready_for_shortcut = true;
-
-                            state.shortcut_sent = false;
-                            state.search_enable = false;
-                        } else if state.current == 3 {
-                            state.ready_for_shortcut = false;
-                            state.ready_for_search = false;
-                        }
-                        Command::none()
-                    }
+                    Message::BackPressed => match state.page.previous() {
+                        Some(page) => state.enter(page),
+                        None => Command::none(),
+                    },
+                    Message::NextPressed => match state.next_page() {
+                        Some(page) => state.enter(page),
+                        None => Command::none(),
+                    },
                     Message::UserOperationSeleted(userop) => {
                         //    make list_radio_enable true when switched to List,ready for
                         //scription
@@ -945,32 +1682,180 @@ impl Application for App {
                             if let Some(addr) = m {
                                 state.ipaddr = addr;
                             }
+                            // cable or wifi may have changed since start
+                            state.lan_addrs = lan_addrs().unwrap_or_default();
+                            state.refresh_qr();
                             state.ready_for_lookup = false;
-                            state.ready_for_pcmod = true;
 
-                            // in case step back from step 3 or step one,status not update
-                            state.pcip_modified = false;
-                            state.rootca_to_sysstore = false;
-                            state.server_certs_install = false;
-                            Command::none()
+                            // in case step back from the shortcut page,status not update
+                            state.redo(Page::SetIp);
+                            state.plan_page()
                         }
                         _ => Command::none(),
                     },
-                    Message::PCIPChanged(_) => {
-                        state.pcip_modified = true;
-                        state.ready_for_pcmod = false;
-                        state.ready_for_sysstore = true;
+                    Message::LanAddrSelected(i) => {
+                        // the chain of steps is still running on the old address
+                        let addr = match state.lan_addrs.get(i) {
+                            Some(addr) if !state.set_ip_busy() => addr.ip.to_string(),
+                            _ => return Command::none(),
+                        };
+                        if addr == state.ipaddr {
+                            return Command::none();
+                        }
+                        // redo every step for the new address
+                        state.ipaddr = addr.clone();
+                        state.refresh_qr();
+                        state.redo(Page::SetIp);
+                        Command::batch(vec![
+                            Command::perform(
+                                remember_ip(addr)
+                                    .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                                Message::LanChoiceSaved,
+                            ),
+                            state.plan_page(),
+                        ])
+                    }
+                    Message::Planned(result) => {
+                        if state.plan_pending != Some(state.page) {
+                            return Command::none();
+                        }
+                        match result {
+                            // a plan for an address given up on meanwhile
+                            Ok(plan) if plan.ip != state.ipaddr => {}
+                            Ok(plan) => state.plan = Some(plan),
+                            Err(e) => {
+                                let msg = match e {
+                                    CMDError::Failed(msg) => msg,
+                                    _ => String::new(),
+                                };
+                                state.plan_message = Some((trf("plan.failed", &[&msg]), true));
+                            }
+                        }
+                        Command::none()
+                    }
+                    Message::ApplyPressed => {
+                        if state.plan.is_none() || state.plan_pending != Some(state.page) {
+                            return Command::none();
+                        }
+                        state.plan_pending = None;
+                        state.plan = None;
+                        state.plan_message = None;
+                        // the first step not done yet,each one starts the next
+                        let page = state.page;
+                        state.step_errors.retain(|(s, _)| Page::of(*s) != page);
+                        state.running = page.steps().iter().copied().find(|s| !state.step_done(*s));
+                        Command::none()
+                    }
+                    Message::PlanExportPressed => {
+                        let plan = match &state.plan {
+                            Some(plan) => plan.clone(),
+                            None => return Command::none(),
+                        };
+                        let platform = state.platform;
+                        Command::perform(
+                            async move {
+                                let path = plan_path(platform)?;
+                                plan.save(&path).await.map(|_| path)
+                            }
+                            .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::PlanExported,
+                        )
+                    }
+                    Message::PlanExported(result) => {
+                        state.plan_message = Some(match result {
+                            Ok(path) => (trf("plan.exported", &[&path.display()]), false),
+                            Err(CMDError::Failed(msg)) => {
+                                (trf("plan.export_failed", &[&msg]), true)
+                            }
+                            Err(_) => (trf("plan.export_failed", &[&""]), true),
+                        });
+                        Command::none()
+                    }
+                    Message::LangChosen(chosen) => {
+                        if chosen.needs_cjk() && !state.cjk_font {
+                            // its glyphs are missing until simhei is loaded at startup
+                            state.lang_pending = Some(chosen);
+                        } else {
+                            state.lang_pending = None;
+                            set_lang(chosen);
+                            state.refresh_qr();
+                        }
+                        // if it isn't saved the next start just follows the locale again
+                        Command::perform(remember_lang(chosen), |_| Message::LangSaved)
+                    }
+                    Message::PortInputChanged(value) => {
+                        state.port_input = value;
+                        Command::none()
+                    }
+                    Message::PortApplyPressed => {
+                        let port = match state.port_input.trim().parse::<u16>() {
+                            Ok(port) if port > 0 => port,
+                            _ => {
+                                state.port_error = Some(tr("server.port_invalid").into());
+                                return Command::none();
+                            }
+                        };
+                        state.port_error = None;
+                        let platform = state.platform;
+                        Command::perform(
+                            async move { set_port(platform, port).await.map(|_| port) }
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::PortApplied,
+                        )
+                    }
+                    Message::PortApplied(result) => {
+                        match result {
+                            Ok(port) => {
+                                state.port = port;
+                                state.refresh_qr();
+                                // ankisyncd reads the port on start only
+                                if let ServerState::Running { .. } = supervisor().status().state {
+                                    supervisor().restart();
+                                }
+                            }
+                            Err(CMDError::Failed(msg)) => state.port_error = Some(msg),
+                            Err(_) => {}
+                        }
+                        Command::none()
+                    }
+                    Message::RootCaShared(Event::Received(e)) => {
+                        state.rootca_share_error = e;
+                        Command::none()
+                    }
+                    Message::StepProgressed(StepEvent::Logged(line)) => {
+                        state.step_log.push(line);
+                        Command::none()
+                    }
+                    Message::StepProgressed(StepEvent::Finished(step, Ok(()))) => {
+                        state.finish_step(step);
                         Command::none()
                     }
-                    Message::CAImported(_) => {
-                        state.ready_for_sysstore = false;
-                        state.rootca_to_sysstore = true;
-                        state.ready_for_certsin = true;
+                    Message::StepProgressed(StepEvent::Finished(step, Err(e))) => {
+                        let msg = match e {
+                            CMDError::Failed(msg) => msg,
+                            _ => String::new(),
+                        };
+                        state.fail_step(step, msg);
+                        // saved right away,it's what a bug report needs
+                        let save = state.save_log();
+                        if Page::of(step) == state.page {
+                            // applying the plan again retries from the failed step
+                            Command::batch([save, state.plan_page()])
+                        } else {
+                            save
+                        }
+                    }
+                    Message::LogToggled => {
+                        state.log_expanded = !state.log_expanded;
                         Command::none()
                     }
-                    Message::ServCertsInstalled(_) => {
-                        state.ready_for_certsin = false;
-                        state.server_certs_install = true;
+                    Message::LogSavePressed => state.save_log(),
+                    Message::LogSaved(result) => {
+                        state.log_message = Some(match result {
+                            Ok(path) => (trf("log.saved", &[&path.display()]), false),
+                            Err(CMDError::Failed(msg)) => (trf("log.save_failed", &[&msg]), true),
+                            Err(_) => (trf("log.save_failed", &[&""]), true),
+                        });
                         Command::none()
                     }
                     Message::Submit(username, password) => {
@@ -978,7 +1863,7 @@ impl Application for App {
                         let tips = match state.selection {
                             Some(UserOperation::Delete) => {
                                 if username.is_empty() {
-                                    "用户名为空"
+                                    tr("users.name_empty")
                                 } else {
                                     ""
                                 }
@@ -986,7 +1871,7 @@ impl Application for App {
                             Some(UserOperation::List) | None => "",
                             _ => {
                                 if username.is_empty() || password.is_empty() {
-                                    "用户名或密码为空"
+                                    tr("users.name_or_pass_empty")
                                 } else {
                                     ""
                                 }
@@ -1003,25 +1888,317 @@ impl Application for App {
                             Message::ExternalCMD,
                         )
                     }
-                    Message::ExternalCMD(_) => {
+                    Message::ExternalCMD(Ok(())) => {
+                        let filled = !state.username.is_empty() && !state.password.is_empty();
+                        match state.selection {
+                            Some(UserOperation::Add | UserOperation::Pass) if filled => {
+                                state.bundle_account = Some(Credentials {
+                                    username: state.username.clone(),
+                                    password: state.password.clone(),
+                                });
+                            }
+                            Some(UserOperation::Delete)
+                                if state
+                                    .bundle_account
+                                    .as_ref()
+                                    .map_or(false, |a| a.username == state.username) =>
+                            {
+                                state.bundle_account = None;
+                                state.bundle_with_account = false;
+                            }
+                            _ => {}
+                        }
                         state.username.clear();
                         state.password.clear();
                         Command::none()
                     }
+                    Message::ExternalCMD(Err(e)) => {
+                        // duplicate/missing user or locked db,keep input for a retry
+                        if let CMDError::Failed(msg) = e {
+                            state.useracnt_optips = msg;
+                        }
+                        Command::none()
+                    }
+                    Message::RenewPressed => {
+                        state.cert_renewing = true;
+                        state.cert_message = None;
+                        Command::perform(
+                            renew_servcerts(state.platform, state.ipaddr.clone())
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::CertRenewed,
+                        )
+                    }
+                    Message::CertRenewed(result) => {
+                        state.cert_renewing = false;
+                        match result {
+                            Ok(info) => {
+                                state.cert_info = Some(info);
+                                state.cert_message = None;
+                            }
+                            Err(CMDError::Failed(msg)) => state.cert_message = Some(msg),
+                            Err(_) => state.cert_message = Some(String::new()),
+                        }
+                        Command::none()
+                    }
+                    Message::UninstallPressed => {
+                        if !state.uninstall_confirming {
+                            state.uninstall_confirming = true;
+                            return Command::none();
+                        }
+                        state.uninstall_confirming = false;
+                        state.uninstalling = true;
+                        Command::perform(
+                            uninstall(state.platform).map(|r| r.map_err(|_| CMDError::ExcError)),
+                            Message::Uninstalled,
+                        )
+                    }
+                    Message::Uninstalled(result) => {
+                        state.uninstalling = false;
+                        if let Ok(report) = result {
+                            if report.is_clean() {
+                                state.deploy_count = 0;
+                                state.deployed = false;
+                                state.cert_info = None;
+                            }
+                            state.uninstall_report = Some(report);
+                        }
+                        Command::none()
+                    }
                     Message::TogglerChanged(enable) => {
                         state.send_rootca_to_phone = enable;
                         Command::none()
                     }
 
-                    Message::MSLNKSent(_) => {
-                        state.ready_for_shortcut = false;
-                        state.ready_for_search = true;
-                        state.shortcut_sent = true;
+                    Message::ServerStartPressed => {
+                        supervisor().start();
+                        Command::none()
+                    }
+                    Message::ServerStopPressed => {
+                        supervisor().stop();
+                        Command::none()
+                    }
+                    Message::ServerRestartPressed => {
+                        supervisor().restart();
+                        Command::none()
+                    }
+                    Message::ServerStatusChanged(status) => {
+                        state.server_status = status;
+                        Command::none()
+                    }
+                    Message::DiagUserChanged(value) => {
+                        state.diag_user = value;
+                        Command::none()
+                    }
+                    Message::DiagPassChanged(value) => {
+                        state.diag_pass = value;
+                        Command::none()
+                    }
+                    Message::DiagnosePressed => {
+                        state.diagnosing = true;
+                        state.diag_error = None;
+                        // without an account only port and cert are checked
+                        let credentials = if state.diag_user.is_empty() {
+                            None
+                        } else {
+                            Some(Credentials {
+                                username: state.diag_user.clone(),
+                                password: state.diag_pass.clone(),
+                            })
+                        };
+                        Command::perform(
+                            diagnose(state.ipaddr.clone(), credentials)
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::Diagnosed,
+                        )
+                    }
+                    Message::BackupsListed(result) => {
+                        match result {
+                            Ok(archives) => {
+                                state.backups = archives
+                                    .into_iter()
+                                    .map(|a| (a, button::State::new()))
+                                    .collect()
+                            }
+                            Err(CMDError::Failed(msg)) => state.backup_message = Some((msg, true)),
+                            Err(_) => {}
+                        }
+                        Command::none()
+                    }
+                    Message::BackupPressed => {
+                        state.backing_up = true;
+                        state.restore_confirming = None;
+                        state.backup_message = None;
+                        Command::perform(
+                            backup_now().map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::BackedUp,
+                        )
+                    }
+                    Message::BackedUp(result) => {
+                        state.backing_up = false;
+                        state.backup_message = Some(match result {
+                            Ok(archive) => (trf("backup.done", &[&archive.path.display()]), false),
+                            Err(CMDError::Failed(msg)) => (trf("backup.failed", &[&msg]), true),
+                            Err(_) => (tr("backup.failed_unknown").to_owned(), true),
+                        });
+                        Command::perform(
+                            list_backups().map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::BackupsListed,
+                        )
+                    }
+                    Message::RestorePressed(i) => {
+                        if state.restore_confirming != Some(i) {
+                            state.restore_confirming = Some(i);
+                            return Command::none();
+                        }
+                        let archive = match state.backups.get(i) {
+                            Some((archive, _)) => archive.path.clone(),
+                            None => return Command::none(),
+                        };
+                        state.restoring = true;
+                        state.backup_message = None;
+                        Command::perform(
+                            restore_backup(state.platform, archive)
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::Restored,
+                        )
+                    }
+                    Message::Restored(result) => {
+                        state.restoring = false;
+                        state.restore_confirming = None;
+                        state.backup_message = Some(match result {
+                            Ok(saved) => (trf("backup.restored", &[&saved.display()]), false),
+                            Err(CMDError::Failed(msg)) => {
+                                (trf("backup.restore_failed", &[&msg]), true)
+                            }
+                            Err(_) => (tr("backup.restore_failed_unknown").to_owned(), true),
+                        });
+                        Command::none()
+                    }
+                    Message::ConfigBackupsListed(result) => {
+                        match result {
+                            Ok(found) => {
+                                state.config_backups = found
+                                    .into_iter()
+                                    .map(|b| (b, button::State::new()))
+                                    .collect()
+                            }
+                            Err(CMDError::Failed(msg)) => state.config_message = Some((msg, true)),
+                            Err(_) => {}
+                        }
+                        Command::none()
+                    }
+                    Message::ConfigRestorePressed(i) => {
+                        if state.config_restore_confirming != Some(i) {
+                            state.config_restore_confirming = Some(i);
+                            return Command::none();
+                        }
+                        let backup = match state.config_backups.get(i) {
+                            Some((backup, _)) => backup.clone(),
+                            None => return Command::none(),
+                        };
+                        state.config_restoring = true;
+                        state.config_message = None;
+                        Command::perform(
+                            restore_config_backup(backup)
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::ConfigRestored,
+                        )
+                    }
+                    Message::ConfigRestored(result) => {
+                        state.config_restoring = false;
+                        state.config_restore_confirming = None;
+                        state.config_message = Some(match result {
+                            Ok(file) => (trf("config_backup.restored", &[&file.display()]), false),
+                            Err(CMDError::Failed(msg)) => {
+                                (trf("config_backup.restore_failed", &[&msg]), true)
+                            }
+                            Err(_) => (tr("backup.restore_failed_unknown").to_owned(), true),
+                        });
+                        // the restore itself was backed up
+                        Command::perform(
+                            list_config_backups(state.platform)
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::ConfigBackupsListed,
+                        )
+                    }
+                    Message::WatchToggled(enable) => {
+                        state.watch_addr = enable;
+                        Command::none()
+                    }
+                    Message::BundleAccountToggled(enable) => {
+                        state.bundle_with_account = enable;
+                        Command::none()
+                    }
+                    Message::BundleZipToggled(enable) => {
+                        state.bundle_zip = enable;
+                        Command::none()
+                    }
+                    Message::ExportPressed => {
+                        let out = match bundle_path(state.platform, state.bundle_zip) {
+                            Ok(out) => out,
+                            Err(e) => {
+                                state.bundle_message = Some((trf("bundle.failed", &[&e]), true));
+                                return Command::none();
+                            }
+                        };
+                        let account = state
+                            .bundle_account
+                            .clone()
+                            .filter(|_| state.bundle_with_account);
+                        state.exporting = true;
+                        state.bundle_message = None;
+                        let ipaddr = state.ipaddr.clone();
+                        let zip = state.bundle_zip;
+                        Command::perform(
+                            async move { export_bundle(&ipaddr, out, zip, account).await }
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::Exported,
+                        )
+                    }
+                    Message::Exported(result) => {
+                        state.exporting = false;
+                        state.bundle_message = Some(match result {
+                            Ok(path) => (trf("bundle.exported", &[&path.display()]), false),
+                            Err(CMDError::Failed(msg)) => (trf("bundle.failed", &[&msg]), true),
+                            Err(_) => (tr("bundle.failed_unknown").to_owned(), true),
+                        });
                         Command::none()
                     }
-                    Message::SearchEnabled(_) => {
-                        state.ready_for_search = false;
-                        state.search_enable = true;
+                    Message::AddrWatched(events) => {
+                        let mut renewed = false;
+                        for event in &events {
+                            match event {
+                                WatchEvent::AddrChanged { new: Some(ip), .. } => {
+                                    state.ipaddr = ip.clone();
+                                    state.refresh_qr();
+                                }
+                                WatchEvent::CertRenewed { .. } => renewed = true,
+                                _ => {}
+                            }
+                        }
+                        state.watch_events.extend(events);
+                        let tail = state.watch_events.len().saturating_sub(WATCH_EVENT_LINES);
+                        state.watch_events.drain(..tail);
+                        if !renewed {
+                            return Command::none();
+                        }
+                        Command::perform(
+                            server_cert_info()
+                                .map(|r| r.map_err(|e| CMDError::Failed(e.to_string()))),
+                            Message::CertRenewed,
+                        )
+                    }
+                    Message::Diagnosed(result) => {
+                        state.diagnosing = false;
+                        match result {
+                            Ok(results) => state.diag_results = results,
+                            Err(e) => {
+                                state.diag_results.clear();
+                                if let CMDError::Failed(msg) = e {
+                                    state.diag_error = Some(msg);
+                                }
+                            }
+                        }
                         Command::none()
                     }
                     _ => Command::none(),
@@ -1032,38 +2209,111 @@ impl Application for App {
     fn view(&mut self) -> Element<Message> {
         match self {
             App::Loading => loading_message(),
-            App::Loaded(State {
-                verok,
-                deploy_count,
-                back_button,
-                next_button,
-                current,
-
-                selection,
-                username,
-                password,
-                state_user,
-                state_pass,
-                submit_button,
-                useracnt_optips,
-
-                pcip_modified,
-                server_certs_install,
-                rootca_to_sysstore,
-                send_rootca_to_phone,
-                ipaddr,
-                state_sync,
-                state_media,
+            App::Loaded(state) => {
+                let has_previous = state.page.previous().is_some();
+                let can_continue = state.next_page().is_some();
+                let set_ip_busy = state.set_ip_busy();
+                let done: Vec<Page> = Page::all()
+                    .into_iter()
+                    .filter(|p| {
+                        !p.steps().is_empty() && p.steps().iter().all(|s| state.step_done(*s))
+                    })
+                    .collect();
+                let State {
+                    anki_version,
+                    deploy_count,
+                    back_button,
+                    next_button,
+                    page,
+
+                    selection,
+                    username,
+                    password,
+                    state_user,
+                    state_pass,
+                    submit_button,
+                    useracnt_optips,
+                    cert_info,
+                    renew_button,
+                    cert_renewing,
+                    cert_message,
+                    deployed,
+                    uninstall_button,
+                    uninstall_confirming,
+                    uninstalling,
+                    uninstall_report,
+
+                    pcip_modified,
+                    server_certs_install,
+                    rootca_to_sysstore,
+                    send_rootca_to_phone,
+                    ipaddr,
+                    port,
+                    port_input,
+                    port_state,
+                    port_button,
+                    port_error,
+                    lan_addrs,
+                    qr_codes,
+                    rootca_share_error,
+                    state_sync,
+                    state_media,
 
-                shortcut_sent,
-                search_enable,
-                ..
-            }) => {
+                    shortcut_sent,
+                    search_enable,
+
+                    server_status,
+                    server_start_button,
+                    server_stop_button,
+                    server_restart_button,
+                    diag_user,
+                    diag_pass,
+                    diag_user_state,
+                    diag_pass_state,
+                    diagnose_button,
+                    diagnosing,
+                    diag_results,
+                    diag_error,
+                    backups,
+                    backup_button,
+                    backing_up,
+                    restore_confirming,
+                    restoring,
+                    backup_message,
+                    config_backups,
+                    config_restore_confirming,
+                    config_restoring,
+                    config_message,
+                    watch_addr,
+                    watch_events,
+                    bundle_account,
+                    bundle_with_account,
+                    bundle_zip,
+                    bundle_button,
+                    exporting,
+                    bundle_message,
+                    cjk_font,
+                    lang_pending,
+                    plan_pending,
+                    plan,
+                    plan_message,
+                    plan_scroll,
+                    apply_button,
+                    plan_export_button,
+                    step_log,
+                    step_errors,
+                    log_expanded,
+                    log_message,
+                    log_scroll,
+                    log_toggle_button,
+                    log_save_button,
+                    ..
+                } = state;
                 let mut controls = Row::new();
 
-                if Self::has_previous(*current) {
+                if has_previous {
                     controls = controls.push(
-                        button(back_button, "上一步")
+                        button(back_button, tr("common.back"))
                             .on_press(Message::BackPressed)
                             .style(style::Button::Secondary),
                     );
@@ -1071,17 +2321,38 @@ impl Application for App {
 
                 controls = controls.push(Space::with_width(Length::Fill));
 
-                if Self::can_continue(*current, *send_rootca_to_phone) && *verok {
+                if can_continue {
                     controls = controls.push(
-                        button(next_button, "下一步")
+                        button(next_button, tr("common.next"))
                             .on_press(Message::NextPressed)
                             .style(style::Button::Primary),
                     );
                 }
-                let step_view = match current {
-                    0 => Self::welcome(
+                let plan_section = if *plan_pending == Some(*page) {
+                    Self::plan_section(
+                        plan.as_ref(),
+                        plan_message.as_ref(),
+                        plan_scroll,
+                        apply_button,
+                        plan_export_button,
+                    )
+                } else {
+                    Column::new()
+                };
+                let log_section = Self::step_log_section(
+                    *page,
+                    step_log,
+                    step_errors,
+                    *log_expanded,
+                    log_message.as_ref(),
+                    log_scroll,
+                    log_toggle_button,
+                    log_save_button,
+                );
+                let step_view = match page {
+                    Page::Welcome => Self::welcome(
                         *deploy_count,
-                        *verok,
+                        *anki_version,
                         *selection,
                         username,
                         password,
@@ -1089,18 +2360,49 @@ impl Application for App {
                         state_pass,
                         submit_button,
                         useracnt_optips,
-                    ),
-                    1 => Self::set_ip(
+                        Self::cert_status(
+                            cert_info.as_ref(),
+                            ipaddr,
+                            *cert_renewing,
+                            cert_message.as_deref(),
+                            renew_button,
+                        ),
+                        Self::uninstall_section(
+                            *deployed,
+                            *uninstall_confirming,
+                            *uninstalling,
+                            uninstall_report.as_ref(),
+                            uninstall_button,
+                        ),
+                    )
+                    .push(Self::lang_section(*lang_pending, *cjk_font)),
+                    Page::SetIp => Self::set_ip(
                         *pcip_modified,
                         *server_certs_install,
                         *rootca_to_sysstore,
                         *send_rootca_to_phone,
                         ipaddr,
+                        *port,
+                        lan_addrs,
+                        qr_codes,
+                        rootca_share_error.as_deref(),
                         state_sync,
                         state_media,
-                    ),
-                    2 => Self::shortcut_search(*shortcut_sent, *search_enable),
-                    3 => Self::user_manage(
+                    )
+                    .push(Self::port_section(
+                        port_input,
+                        // the chain of steps is still writing the old port
+                        set_ip_busy,
+                        port_error.as_deref(),
+                        port_state,
+                        port_button,
+                    ))
+                    .push(plan_section)
+                    .push(log_section),
+                    Page::Shortcut => Self::shortcut_search(*shortcut_sent, *search_enable)
+                        .push(plan_section)
+                        .push(log_section),
+                    Page::Users => Self::user_manage(
                         *selection,
                         username,
                         password,
@@ -1108,14 +2410,53 @@ impl Application for App {
                         state_pass,
                         submit_button,
                         useracnt_optips,
-                    ),
-                    _ => Self::test(),
+                    )
+                    .push(Self::bundle_section(
+                        bundle_account.as_ref(),
+                        *bundle_with_account,
+                        *bundle_zip,
+                        *exporting,
+                        bundle_message.as_ref(),
+                        bundle_button,
+                    )),
+                    Page::Server => Self::server(
+                        server_status,
+                        server_start_button,
+                        server_stop_button,
+                        server_restart_button,
+                    )
+                    .push(Self::diagnostics(
+                        diag_user,
+                        diag_pass,
+                        *diagnosing,
+                        diag_results,
+                        diag_error.as_deref(),
+                        diag_user_state,
+                        diag_pass_state,
+                        diagnose_button,
+                    ))
+                    .push(Self::backups(
+                        backups,
+                        *backing_up,
+                        *restoring,
+                        *restore_confirming,
+                        backup_message.as_ref(),
+                        backup_button,
+                    ))
+                    .push(Self::config_backups(
+                        config_backups,
+                        *config_restoring,
+                        *config_restore_confirming,
+                        config_message.as_ref(),
+                    ))
+                    .push(Self::addr_watch_section(*watch_addr, watch_events)),
                 };
                 let content: Element<_> = Column::new()
                     .align_items(alignment::Alignment::Center)
                     .max_width(700)
                     // .spacing(20)
                     // .padding(20)
+                    .push(Self::progress(*page, &done))
                     .push(step_view)
                     .push(controls)
                     .into();
@@ -1131,17 +2472,45 @@ impl Application for App {
 }
 #[derive(Debug, Clone)]
 pub struct LoadingConf {
+    platform: &'static dyn Platform,
     verok: bool,
-    deploy_count: u8,
+    anki_version: Option<AnkiVersion>,
+    deploy_count: u32,
+    /// page of the first incomplete step of an interrupted deployment
+    resume_page: Page,
+    /// steps an earlier run finished for ipaddr
+    done_steps: Vec<StepKind>,
+    deployed: bool,
+    cert_info: Option<CertInfo>,
+    ipaddr: String,
+    port: u16,
+    lan_addrs: Vec<LanAddr>,
 }
 
 impl LoadingConf {
     async fn load() -> Result<LoadingConf, LoadError> {
-        let deploy_count = read_parse_deploy_count().await.unwrap();
-        let pc_ver_ok = pc_ver_required();
+        let platform = platform::current();
+        let manifest = Manifest::load(&manifest_path()).await.unwrap_or_default();
+        let anki_version = anki_version(platform).ok();
+        let pc_ver_ok = matches!(anki_version, Some(v) if v.is_supported());
+        // no cert yet before the first deploy
+        let cert_info = server_cert_info().await.ok();
+        let ipaddr = lookup_ip().await.unwrap_or_default();
         Ok(LoadingConf {
+            platform,
             verok: pc_ver_ok,
-            deploy_count,
+            anki_version,
+            deploy_count: manifest.deploy_count,
+            resume_page: manifest.resume_step().map_or(Page::Welcome, Page::of),
+            done_steps: StepKind::all()
+                .into_iter()
+                .filter(|s| manifest.is_done_for(*s, &ipaddr))
+                .collect(),
+            deployed: !manifest.steps.is_empty(),
+            cert_info,
+            ipaddr,
+            port: sync_port().await.unwrap_or(DEFAULT_SYNC_PORT),
+            lan_addrs: lan_addrs().unwrap_or_default(),
         })
     }
 }
@@ -1151,37 +2520,18 @@ pub struct UserAccount {
     password: Option<String>,
 }
 impl UserAccount {
+    /// run the selected operation against the auth db,empty fields do nothing
     async fn userops(self, select: Option<UserOperation>) -> Result<(), CMDError> {
-        match select {
-            Some(UserOperation::Add) => self.add().await,
-            Some(UserOperation::Delete) => self.del().await,
-            Some(UserOperation::Pass) => self.pass().await,
+        let username = self.username.unwrap_or_default();
+        let password = self.password.unwrap_or_default();
+        let filled = !username.is_empty() && !password.is_empty();
+        let result = match select {
+            Some(UserOperation::Add) if filled => addusr(&username, &password).await,
+            Some(UserOperation::Delete) if !username.is_empty() => delusr(&username).await,
+            Some(UserOperation::Pass) if filled => chgepass(&username, &password).await,
             _ => Ok(()),
-        }
-    }
-    async fn add(self) -> Result<(), CMDError> {
-        if self.username.is_some() && self.password.is_some() {
-            if let (Some(username), Some(password)) = (self.username, self.password) {
-                addusr(username, password).unwrap();
-            }
-        }
-        Ok(())
-    }
-    async fn del(self) -> Result<(), CMDError> {
-        if self.username.is_some() {
-            if let Some(username) = self.username {
-                delusr(username).unwrap();
-            }
-        }
-        Ok(())
-    }
-    async fn pass(self) -> Result<(), CMDError> {
-        if self.username.is_some() && self.password.is_some() {
-            if let (Some(username), Some(password)) = (self.username, self.password) {
-                chgepass(username, password).unwrap();
-            }
-        }
-        Ok(())
+        };
+        result.map_err(|e| CMDError::Failed(e.to_string()))
     }
 }
 enum SubscriptionState {
@@ -1203,7 +2553,14 @@ fn list() -> Subscription<Event> {
 async fn list_logic(state: SubscriptionState) -> (Option<Event>, SubscriptionState) {
     match state {
         SubscriptionState::Start => {
-            let users = lsusr().await.unwrap();
+            let users = match lsusr().await {
+                Ok(accounts) => accounts
+                    .into_iter()
+                    .map(|a| a.username)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Err(e) => e.to_string(),
+            };
             (
                 Some(Event::Received(Some(users))),
                 SubscriptionState::Finish,
@@ -1228,8 +2585,9 @@ fn look_up() -> Subscription<Event> {
 async fn look_up_logic(state: SubscriptionState) -> (Option<Event>, SubscriptionState) {
     match state {
         SubscriptionState::Start => {
-            let ip = lookup_ip().unwrap();
-            (Some(Event::Received(Some(ip))), SubscriptionState::Finish)
+            // keep the current address when nothing is found,the page shows it empty
+            let ip = lookup_ip().await.ok();
+            (Some(Event::Received(ip)), SubscriptionState::Finish)
         }
 
         SubscriptionState::Finish => {
@@ -1238,20 +2596,25 @@ async fn look_up_logic(state: SubscriptionState) -> (Option<Event>, Subscription
         }
     }
 }
-/// look up LAN ip address
-fn pcip_mod(ipaddr: &str) -> Subscription<Event> {
+/// serve rootCA.crt for phones,restarted when ipaddr changes
+fn share_rootca(ipaddr: &str) -> Subscription<Event> {
     struct SM;
     subscription::unfold(
-        std::any::TypeId::of::<SM>(),
+        (std::any::TypeId::of::<SM>(), ipaddr.to_owned()),
         SetIPState::Ready(ipaddr.into()),
-        move |state| pcip_mod_logic(state),
+        share_rootca_logic,
     )
 }
-async fn pcip_mod_logic(state: SetIPState) -> (Option<Event>, SetIPState) {
+async fn share_rootca_logic(state: SetIPState) -> (Option<Event>, SetIPState) {
     match state {
         SetIPState::Ready(ipaddr) => {
-            pcip_modify(&ipaddr).await.unwrap();
-            (Some(Event::Received(None)), SetIPState::Finish)
+            let ca_cert = ca_dir().join(ROOTCA_FILE);
+            // only returns when it can't listen
+            let error = serve_rootca(&ipaddr, &ca_cert).await.err();
+            (
+                Some(Event::Received(error.map(|e| e.to_string()))),
+                SetIPState::Finish,
+            )
         }
 
         SetIPState::Finish => {
@@ -1260,98 +2623,170 @@ async fn pcip_mod_logic(state: SetIPState) -> (Option<Event>, SetIPState) {
         }
     }
 }
-///
-fn import_sysstore() -> Subscription<Event> {
-    struct SM;
+/// address watch events,the first batch only says watching started
+fn addr_changes(platform: &'static dyn Platform) -> Subscription<Vec<WatchEvent>> {
+    struct AW;
     subscription::unfold(
-        std::any::TypeId::of::<SM>(),
-        SubscriptionState::Start,
-        move |state| import_sysstore_logic(state),
+        std::any::TypeId::of::<AW>(),
+        None,
+        move |watch| async move {
+            match watch {
+                None => match addr_watch(platform).await {
+                    Ok(watch) => (Some(vec![watch.started()]), Some(watch)),
+                    Err(e) => {
+                        // e.g. no deployment.json yet,retry later
+                        async_std::task::sleep(deploy_core::watch::POLL_INTERVAL).await;
+                        (Some(vec![WatchEvent::failed("watch", e)]), None)
+                    }
+                },
+                Some(mut watch) => {
+                    let events = follow_addr(platform, &mut watch).await;
+                    (Some(events), Some(watch))
+                }
+            }
+        },
     )
 }
-/// import rootCA to system trust store \n
-/// rename .pem to .crr and send to desktop
-async fn import_sysstore_logic(state: SubscriptionState) -> (Option<Event>, SubscriptionState) {
-    match state {
-        SubscriptionState::Start => {
-            import_to_sysstore().await.unwrap();
-            (Some(Event::Received(None)), SubscriptionState::Finish)
-        }
-
-        SubscriptionState::Finish => {
-            let _: () = iced::futures::future::pending().await;
-            unreachable!()
-        }
-    }
+/// ankisyncd status,a new one whenever its state or output changes
+fn server_status() -> Subscription<ServerStatus> {
+    struct SM;
+    subscription::unfold(std::any::TypeId::of::<SM>(), None, |seen| async move {
+        let status = supervisor().changed(seen).await;
+        let generation = Some(status.generation);
+        (Some(status), generation)
+    })
 }
-/// run cmd mkcert to gen server cert and key files
-///
-/// write their paths to file Settings.toml
-fn srvcerts_install(ipaddr: &str) -> Subscription<Event> {
+/// progress of a step run by step_run
+enum StepRunState {
+    Ready(StepKind, String),
+    Running(
+        StepKind,
+        Receiver<LogLine>,
+        JoinHandle<Result<(), ApplicationError>>,
+    ),
+    Finish,
+}
+/// run step for ipaddr,its log lines as they come and then its result
+fn step_run(
+    platform: &'static dyn Platform,
+    step: StepKind,
+    ipaddr: &str,
+) -> Subscription<StepEvent> {
     struct SM;
     subscription::unfold(
-        std::any::TypeId::of::<SM>(),
-        SetIPState::Ready(ipaddr.into()),
-        move |state| srvcerts_install_logic(state),
+        (std::any::TypeId::of::<SM>(), step, ipaddr.to_owned()),
+        StepRunState::Ready(step, ipaddr.into()),
+        move |state| step_run_logic(platform, state),
     )
 }
-async fn srvcerts_install_logic(state: SetIPState) -> (Option<Event>, SetIPState) {
+async fn step_run_logic(
+    platform: &'static dyn Platform,
+    state: StepRunState,
+) -> (Option<StepEvent>, StepRunState) {
     match state {
-        SetIPState::Ready(ipaddr) => {
-            install_servcerts(&ipaddr).await.unwrap();
-            (Some(Event::Received(None)), SetIPState::Finish)
+        StepRunState::Ready(step, ipaddr) => {
+            let (tx, rx) = unbounded();
+            let run = async_std::task::spawn(step_log::capture(step, tx, async move {
+                run_step(platform, step, &ipaddr).await
+            }));
+            (None, StepRunState::Running(step, rx, run))
         }
+        StepRunState::Running(step, rx, run) => match rx.recv().await {
+            Ok(line) => (
+                Some(StepEvent::Logged(line)),
+                StepRunState::Running(step, rx, run),
+            ),
+            // closed once the step is done
+            Err(_) => {
+                let result = run.await.map_err(|e| CMDError::Failed(e.to_string()));
+                (
+                    Some(StepEvent::Finished(step, result)),
+                    StepRunState::Finish,
+                )
+            }
+        },
 
-        SetIPState::Finish => {
+        StepRunState::Finish => {
             let _: () = iced::futures::future::pending().await;
             unreachable!()
         }
     }
 }
-
-fn shortcut() -> Subscription<Event> {
-    struct SM;
-    subscription::unfold(
-        std::any::TypeId::of::<SM>(),
-        SubscriptionState::Start,
-        move |state| shortcut_logic(state),
-    )
+fn watch_event_text(event: &WatchEvent) -> (String, Color) {
+    let ok = Color::from_rgb8(0, 255, 0);
+    let failed = Color::from_rgb8(255, 0, 0);
+    match event {
+        WatchEvent::Started { ip, .. } => (
+            trf(
+                "watch.started",
+                &[&ip.as_deref().unwrap_or(tr("common.none"))],
+            ),
+            Color::from_rgb8(128, 128, 128),
+        ),
+        WatchEvent::AddrChanged { old, new } => (
+            trf(
+                "watch.addr_changed",
+                &[
+                    &old.as_deref().unwrap_or(tr("common.none")),
+                    &new.as_deref().unwrap_or(tr("common.none")),
+                ],
+            ),
+            Color::from_rgb8(128, 128, 128),
+        ),
+        WatchEvent::ConfigUpdated { sync_url } => (trf("watch.config_updated", &[sync_url]), ok),
+        WatchEvent::CertOutdated { ip } => (trf("watch.cert_outdated", &[ip]), failed),
+        WatchEvent::CertRenewed { ip } => (trf("watch.cert_renewed", &[ip]), ok),
+        WatchEvent::Failed { step, error } => (trf("watch.failed", &[step, error]), failed),
+    }
 }
-
-async fn shortcut_logic(state: SubscriptionState) -> (Option<Event>, SubscriptionState) {
-    match state {
-        SubscriptionState::Start => {
-            send_shortcut().await.unwrap();
-            (Some(Event::Received(None)), SubscriptionState::Finish)
-        }
-
-        SubscriptionState::Finish => {
-            let _: () = iced::futures::future::pending().await;
-            unreachable!()
+/// heading of a step in the plan
+fn step_label(step: StepKind) -> &'static str {
+    match step {
+        StepKind::PcipModified => tr("set_ip.pcip"),
+        StepKind::CaImported => tr("set_ip.sysstore"),
+        StepKind::CertsIssued => tr("set_ip.certs"),
+        StepKind::ShortcutSent => tr("shortcut.desktop"),
+        StepKind::StartMenuAdded => tr("shortcut.start_menu"),
+    }
+}
+fn operation_text(op: &Operation) -> String {
+    match op {
+        Operation::CopyFile { from, to } => trf("plan.copy", &[&from.display(), &to.display()]),
+        Operation::WriteFile { path } => trf("plan.write", &[&path.display()]),
+        Operation::EditFile { path, .. } => trf("plan.edit", &[&path.display()]),
+        Operation::SetProfileSyncUrl { profile, from, to } => trf(
+            "plan.profile_url",
+            &[profile, &from.as_deref().unwrap_or(tr("plan.ankiweb")), to],
+        ),
+        Operation::SetEnv { name, value } => trf("plan.env", &[name, value]),
+        Operation::TrustCa { cert } => trf("plan.trust_ca", &[&cert.display()]),
+        Operation::CreateShortcut { target, dir } => {
+            trf("plan.shortcut", &[&target.display(), &dir.display()])
         }
     }
 }
-
-fn seach() -> Subscription<Event> {
-    struct SM;
-    subscription::unfold(
-        std::any::TypeId::of::<SM>(),
-        SubscriptionState::Start,
-        move |state| seach_logic(state),
-    )
+fn check_label(check: Check) -> &'static str {
+    match check {
+        Check::Port => tr("diag.port"),
+        Check::Tls => tr("diag.tls"),
+        Check::HostKey => tr("diag.host_key"),
+        Check::MediaBegin => tr("diag.media_begin"),
+    }
 }
-
-async fn seach_logic(state: SubscriptionState) -> (Option<Event>, SubscriptionState) {
-    match state {
-        SubscriptionState::Start => {
-            add_startmenu().await.unwrap();
-            (Some(Event::Received(None)), SubscriptionState::Finish)
-        }
-
-        SubscriptionState::Finish => {
-            let _: () = iced::futures::future::pending().await;
-            unreachable!()
-        }
+/// what the user should do about problem
+fn problem_tips(problem: &Problem) -> String {
+    match problem {
+        Problem::PortClosed(addr) => trf("diag.port_closed", &[addr]),
+        Problem::Timeout(addr) => trf("diag.timeout", &[addr]),
+        Problem::Network(addr, e) => trf("diag.network", &[addr, e]),
+        Problem::RootCaMissing(_) => tr("diag.rootca_missing").into(),
+        Problem::SanMismatch(ip) => trf("diag.san_mismatch", &[ip]),
+        Problem::UnknownIssuer => tr("diag.unknown_issuer").into(),
+        Problem::CertExpired => tr("diag.cert_expired").into(),
+        Problem::Tls(e) => trf("diag.tls_failed", &[e]),
+        Problem::AuthFailed => tr("diag.auth_failed").into(),
+        Problem::Http(status) => trf("diag.http", &[status]),
+        Problem::Protocol(e) => trf("diag.protocol", &[e]),
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1374,10 +2809,10 @@ impl UserOperation {
 impl From<UserOperation> for String {
     fn from(userop: UserOperation) -> String {
         String::from(match userop {
-            UserOperation::Add => "创建账号",
-            UserOperation::List => "查看账号",
-            UserOperation::Delete => "删除用户",
-            UserOperation::Pass => "修改密码",
+            UserOperation::Add => tr("users.add"),
+            UserOperation::List => tr("users.list"),
+            UserOperation::Delete => tr("users.delete"),
+            UserOperation::Pass => tr("users.pass"),
         })
     }
 }
@@ -0,0 +1,169 @@
+use deploy_core::i18n::tr;
+use deploy_core::manifest::StepKind;
+
+/// what the wizard knows that decides whether a page is complete
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// PC Anki was found and is recent enough
+    pub anki_supported: bool,
+    /// the user says the phone trusts the root CA
+    pub phone_trusts_ca: bool,
+    /// steps an earlier run finished for the current address
+    pub done_before: &'a [StepKind],
+}
+/// pages of the wizard,Page::all gives their order
+///
+/// each page declares its title,the deployment steps it runs,the pages it
+/// depends on and when it's complete,navigation and the progress row are
+/// worked out from that,views live with the state in ui.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Welcome,
+    SetIp,
+    Shortcut,
+    Users,
+    Server,
+}
+impl Page {
+    pub fn all() -> [Page; 5] {
+        [
+            Page::Welcome,
+            Page::SetIp,
+            Page::Shortcut,
+            Page::Users,
+            Page::Server,
+        ]
+    }
+    pub fn title(self) -> &'static str {
+        match self {
+            Page::Welcome => tr("step.welcome"),
+            Page::SetIp => tr("step.set_ip"),
+            Page::Shortcut => tr("step.shortcut"),
+            Page::Users => tr("step.users"),
+            Page::Server => tr("step.server"),
+        }
+    }
+    /// deployment steps run once the page's plan is applied,each starts the next
+    pub fn steps(self) -> &'static [StepKind] {
+        match self {
+            Page::SetIp => &[
+                StepKind::PcipModified,
+                StepKind::CaImported,
+                StepKind::CertsIssued,
+            ],
+            Page::Shortcut => &[StepKind::ShortcutSent, StepKind::StartMenuAdded],
+            Page::Welcome | Page::Users | Page::Server => &[],
+        }
+    }
+    /// pages which have to be complete before this one can be entered
+    pub fn requires(self) -> &'static [Page] {
+        match self {
+            Page::Welcome => &[],
+            // nothing works with a PC Anki that is too old
+            Page::SetIp | Page::Users => &[Page::Welcome],
+            // shortcuts start ankisyncd,which needs its certs
+            Page::Shortcut | Page::Server => &[Page::SetIp],
+        }
+    }
+    /// completion condition,pages requiring this one can't be entered before
+    pub fn is_complete(self, progress: &Progress) -> bool {
+        match self {
+            Page::Welcome => progress.anki_supported,
+            // the phone has to trust the root CA too,unless an earlier run did it all
+            Page::SetIp => {
+                progress.phone_trusts_ca
+                    || self
+                        .steps()
+                        .iter()
+                        .all(|s| progress.done_before.contains(s))
+            }
+            Page::Shortcut | Page::Users | Page::Server => true,
+        }
+    }
+    /// every page this one requires is complete
+    pub fn can_enter(self, progress: &Progress) -> bool {
+        self.requires().iter().all(|p| p.is_complete(progress))
+    }
+    /// page whose steps include step
+    pub fn of(step: StepKind) -> Page {
+        Page::all()
+            .into_iter()
+            .find(|p| p.steps().contains(&step))
+            .expect("every step is on a page")
+    }
+    pub fn number(self) -> usize {
+        Page::all()
+            .iter()
+            .position(|p| *p == self)
+            .expect("all has every page")
+    }
+    pub fn previous(self) -> Option<Page> {
+        self.number().checked_sub(1).map(|i| Page::all()[i])
+    }
+    /// the page after this one,passing over pages whose steps are all done
+    ///
+    /// pages without steps are never passed over
+    pub fn next(self, step_done: impl Fn(StepKind) -> bool) -> Option<Page> {
+        Page::all()
+            .into_iter()
+            .skip(self.number() + 1)
+            .find(|p| p.steps().is_empty() || !p.steps().iter().all(|s| step_done(*s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigation_follows_declarations() {
+        // steps are declared in wizard order,each on one page
+        let declared: Vec<StepKind> = Page::all()
+            .iter()
+            .flat_map(|p| p.steps().iter().copied())
+            .collect();
+        assert_eq!(declared, StepKind::all());
+        assert!(StepKind::all()
+            .iter()
+            .all(|s| Page::of(*s).steps().contains(s)));
+        // a page only requires pages before it
+        for page in Page::all() {
+            assert!(page.requires().iter().all(|r| r.number() < page.number()));
+        }
+        assert_eq!(Page::Welcome.previous(), None);
+        assert_eq!(Page::Shortcut.previous(), Some(Page::SetIp));
+        assert_eq!(Page::Server.next(|_| false), None);
+    }
+
+    #[test]
+    fn next_skips_pages_whose_steps_are_done() {
+        assert_eq!(Page::Welcome.next(|_| false), Some(Page::SetIp));
+        let set_ip_done = |s: StepKind| Page::of(s) == Page::SetIp;
+        assert_eq!(Page::Welcome.next(set_ip_done), Some(Page::Shortcut));
+        // Users has no steps,it's never passed over
+        assert_eq!(Page::Welcome.next(|_| true), Some(Page::Users));
+        assert_eq!(Page::SetIp.next(|_| true), Some(Page::Users));
+    }
+
+    #[test]
+    fn pages_wait_for_what_they_require() {
+        let mut progress = Progress {
+            anki_supported: false,
+            phone_trusts_ca: false,
+            done_before: &[],
+        };
+        assert!(Page::Welcome.can_enter(&progress));
+        assert!(!Page::SetIp.can_enter(&progress));
+        progress.anki_supported = true;
+        assert!(Page::SetIp.can_enter(&progress) && Page::Users.can_enter(&progress));
+        assert!(!Page::Shortcut.can_enter(&progress));
+        progress.phone_trusts_ca = true;
+        assert!(Page::Shortcut.can_enter(&progress) && Page::Server.can_enter(&progress));
+
+        progress.phone_trusts_ca = false;
+        progress.done_before = Page::SetIp.steps();
+        assert!(Page::SetIp.is_complete(&progress));
+        progress.done_before = &[StepKind::PcipModified];
+        assert!(!Page::SetIp.is_complete(&progress));
+    }
+}
@@ -1,143 +1,87 @@
-use winreg::enums::HKEY_CURRENT_USER;
-use winreg::RegKey;
-mod error;
-use crate::error::ApplicationError;
-use async_std::io::prelude::BufReadExt;
-use async_std::io::{BufReader, BufWriter,WriteExt};
-use async_std::prelude::*;
-use std::env;
-use std::net::UdpSocket;
-use std::path::{Path, PathBuf};
- fn output_user_profile() -> Result<String, ApplicationError> {
-    // return
-    // userprofile:C:\\Users\\Admin
-    let hklm = RegKey::predef(HKEY_CURRENT_USER);
-    let cur_ver = hklm.open_subkey("Volatile Environment")?;
-    let userprofile: String = cur_ver.get_value("USERPROFILE")?;
-    Ok(userprofile)
-}
-fn addon_ankisyncd_dir() -> Result<PathBuf, ApplicationError> {
-    let usrname_profile = output_user_profile()?;
-    let addon_dir = Path::new(&usrname_profile)
-        .join(r"AppData\Roaming\Anki2\addons21");
-    let ankisyncd_dir = addon_dir.join("ankisyncd");
-
-    Ok(ankisyncd_dir)
-}
-/// copy files from current path to pc anki folder path
-/// 
-/// create dir if ankisyncd dir not exist in pc anki folder path
-async fn copy_addon(ankisyncd_dir: &PathBuf) -> Result<(), ApplicationError> {
-    let cwd = env::current_dir()?;
+//! console front end keeping PC Anki's sync address on the LAN address
+//!
+//! lives in the deployer's pre dir,the work itself is done by deploy_core
+pub use deploy_core::{addon_config, cert_info, error, i18n, interfaces, qr, watch};
+use deploy_core::addon_config::{Scheme, SyncAddr, DEFAULT_SYNC_PORT};
+use deploy_core::anki::SyncTarget;
+use deploy_core::cert_info::CertInfo;
+use deploy_core::error::ApplicationError;
+use deploy_core::i18n::{tr, trf};
+use deploy_core::logic::{
+    addon_src, addr_watch, copy_addon, follow_addr, lookup_ip, root_dir, sync_target,
+};
+use deploy_core::platform;
+use deploy_core::qr::{phone_urls, terminal_qr};
+use std::path::Path;
 
-    // create dir if not exist
-    if !ankisyncd_dir.exists() {
-        async_std::fs::create_dir(&ankisyncd_dir).await?;
-    // read files names from deployer's addon dir
-    let server_addon_dir = cwd.join("ankisyncd");
-    let mut entries = async_std::fs::read_dir(&server_addon_dir).await?;
-    while let Some(res) = entries.next().await {
-        let entry = res?.path();
-        let f = entry.file_name();
-        let dst_path = ankisyncd_dir.join(f.unwrap());
-
-        async_std::fs::copy(entry, &dst_path).await?;
-    }
+/// point PC Anki at ipaddr:port and print addrs in console
+pub async fn set_pcip(
+    target: &SyncTarget,
+    ipaddr: &str,
+    port: u16,
+    scheme: Scheme,
+) -> Result<(), ApplicationError> {
+    let addr = SyncAddr::new(scheme, ipaddr, port);
+    let ip_changed = target.set(&addr).await?;
 
-    
+    if ip_changed {
+        println!("{}", tr("updateaddr.ip_changed"));
+    } else {
+        println!("{}", tr("updateaddr.fill_in"));
     }
-    Ok(())
-}
-/// https not finished
-async fn set_pcip(ankisyncd_dir: PathBuf, ipaddr: &str) -> Result<(), ApplicationError> {
-    let conf_file = ankisyncd_dir.join("config.json");
-    let b = Vec::new();
-    let f = async_std::fs::File::open(conf_file.clone()).await?;
-    let mut lines = BufReader::new(f).lines();
-let mut ip_changed=false;
-    let mut buf = BufWriter::new(b);
-    while let Some(line) = lines.next().await {
-        let l = line?;
-        let cont = if l.contains("syncaddr") {
-            if !l.contains(ipaddr){
-                ip_changed=true;
-            }
-            format!("\"syncaddr\":\"https://{}:27701/\"", &ipaddr)
-        } else {
-            l
-        };
-   
-        if ip_changed {
-            buf.write(cont.as_bytes()).await?;
-            println!("检测到IP地址发生改变，将下面的地址填写到安卓Ankidroid相应界面，电脑Anki重新打开");
-            println!("同步地址：\n https://{}:27701",ipaddr);
-            println!("媒体文件同步地址：\n https://{}:27701/msync",ipaddr);
+    for (label, url) in phone_urls(&addr) {
+        println!("{}\n {}", trf("common.label", &[&label]), url);
+        // scan with the phone instead of typing,skip if the url doesn't fit
+        if let Ok(qr) = terminal_qr(&url) {
+            println!("{}", qr);
         }
-   
     }
     Ok(())
 }
-///http print addr in console 
-async fn set_pcip_http(ankisyncd_dir: PathBuf, ipaddr: &str) -> Result<(), ApplicationError> {
-    let conf_file = ankisyncd_dir.join("config.json");
-    let f = async_std::fs::File::open(conf_file.clone()).await?;
-    let mut lines = BufReader::new(f).lines();
-let mut ip_changed=false;
-let mut file_string=String::new();
-    // let mut buf = BufWriter::new(b);
-    while let Some(line) = lines.next().await {
-        let l = line?;
-        let cont = if l.contains("syncaddr") {
-            if !l.contains(ipaddr){
-                ip_changed=true;
-            }
-            format!("\"syncaddr\":\"http://{}:27701/\"", &ipaddr)
-        } else {
-            l
-        };
-        // buf.write(cont.as_bytes()).await?;
-        file_string.push_str(&format!("{}\n",&cont));
-   
-    }
-    let mut f = async_std::fs::File::create(conf_file).await?;
-    f.write(file_string.as_bytes()).await?;
-    // f.write(&buf.buffer());
-    if ip_changed {
-           
-        println!("检测到IP地址发生改变，将下面的地址填写到安卓Ankidroid相应界面，电脑Anki重新打开");
-        println!("同步地址：\n http://{}:27701",ipaddr);
-        println!("媒体文件同步地址：\n http://{}:27701/msync",ipaddr);
-    }else {
-    
-        println!("将下面的地址填写到安卓Ankidroid相应界面，电脑Anki重新打开");
-        println!("同步地址：\n http://{}:27701",ipaddr);
-        println!("媒体文件同步地址：\n http://{}:27701/msync",ipaddr);
+/// print expiry and addrs of ankisyncd's server cert
+///
+/// warn if current LAN ip is not in it or it expires soon
+pub async fn check_server_cert(cert_file: &Path) -> Result<CertInfo, ApplicationError> {
+    let ipaddr = lookup_ip().await?;
+    let info = CertInfo::load(cert_file).await?;
+    println!("{}", trf("updateaddr.cert_expiry", &[&info.expiry_date()]));
+    println!("{}", trf("updateaddr.cert_sans", &[&info.sans.join(" ")]));
+    if !info.covers(&ipaddr) {
+        println!("{}", trf("updateaddr.ip_missing", &[&ipaddr]));
+    } else if info.needs_renewal(&ipaddr) {
+        println!("{}", trf("updateaddr.expiring", &[&info.days_left()]));
     }
-    Ok(())
+    Ok(info)
 }
-/// lookup ip lan addr
- fn lookup_ip() -> Result<String, ApplicationError> {
-    // look up local ipaddr
-
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    socket
-        .connect("8.8.8.8:80")
-        .expect("Couldn't connect to the server...");
-    let ipaddr = socket.local_addr().unwrap().ip();
-    let ipaddr_str = format!("{}", ipaddr);
-
-    Ok(ipaddr_str)
-}
- /// cp addon dir and set pc anki addr
+/// cp addon dir if PC Anki needs it and set pc anki addr
 /// updateaddr::update_syncaddr().await.unwrap();
-pub async fn update_syncaddr()-> Result<(), ApplicationError> {
-    
-let ipaddr=lookup_ip()?;
-let dir=addon_ankisyncd_dir()?;
-copy_addon(&dir).await?;
-set_pcip_http(dir, &ipaddr).await?;
-
-Ok(())
+pub async fn update_syncaddr() -> Result<(), ApplicationError> {
+    let ipaddr = lookup_ip().await?;
+    let target = sync_target(platform::current())?;
+    if let SyncTarget::Addon(dir) = &target {
+        copy_addon(&addon_src(&root_dir()), dir).await?;
+    }
+    // keep the scheme and port the deployer configured
+    let (scheme, port) = target
+        .current()
+        .await
+        .map(|a| (a.scheme, a.port))
+        .unwrap_or((Scheme::Https, DEFAULT_SYNC_PORT));
+    set_pcip(&target, &ipaddr, port, scheme).await
+}
+/// keep PC Anki on the LAN address until killed,one JSON event per line
+///
+/// follows the interface picked in the deployer and reissues the server cert
+/// when it doesn't cover the new address,see watch::WatchEvent
+pub async fn watch_syncaddr() -> Result<(), ApplicationError> {
+    let platform = platform::current();
+    let mut watch = addr_watch(platform).await?;
+    println!("{}", watch.started().to_json());
+    loop {
+        for event in follow_addr(platform, &mut watch).await {
+            println!("{}", event.to_json());
+        }
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,40 @@
+use deploy_core::i18n;
+use deploy_core::logic::manifest_path;
+use deploy_core::manifest::Manifest;
+use deploy_core::platform;
+use deploy_core::root::{self, DeploymentRoot};
+
+/// updateaddr [--watch],lives in the deployer's pre dir and finds it from its own path
+///
+/// without arguments PC Anki is pointed at the LAN address once,copying the
+/// addon first if its version needs it,
+/// --watch keeps following the address and the server cert with it,printing
+/// one JSON event per line
+fn main() {
+    let watch = std::env::args().skip(1).any(|a| a == "--watch");
+    // resolved once,every path below is absolute
+    match DeploymentRoot::from_exe() {
+        Ok(dir) => root::init(dir),
+        Err(e) => {
+            eprintln!("can't resolve the deployment root: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let result = async_std::task::block_on(async {
+        // the language picked in the deployer,kept in deployment.json next to updateaddr
+        let chosen = Manifest::load(&manifest_path())
+            .await
+            .ok()
+            .and_then(|m| m.lang);
+        i18n::init(platform::current(), chosen.as_deref());
+        if watch {
+            updateaddr::watch_syncaddr().await
+        } else {
+            updateaddr::update_syncaddr().await
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}